@@ -62,6 +62,12 @@ main() {
 	# make_png_with_w "svg/from_lilypond/ledger_line.svg" 427
 	make_png_with_h "svg/public_domain/Sharp.svg" "$SHARP_SIGN_HEIGHT"
 	make_png_with_h "svg/public_domain/DoubleSharp.svg" "$DOUBLESHARP_SIGN_HEIGHT"
+	make_png_with_h "svg/public_domain/Natural.svg" "$SHARP_SIGN_HEIGHT"
+	make_png_with_w "svg/from_lilypond/bass_staff.svg" 1000
+	make_png_with_w "svg/from_lilypond/alto_staff.svg" 1000
+	make_png_with_w "svg/from_lilypond/tenor_staff.svg" 1000
+	make_png_with_w "svg/from_lilypond/treble_8vb_staff.svg" 1000
+	make_png_with_w "svg/from_lilypond/grand_staff.svg" 1000
 }
 
 main
@@ -0,0 +1,426 @@
+//! Where sound goes to and comes from
+//!
+//! The trainer only talks to an `AudioBackend`, so that it can run on real devices, without any
+//! sound at all, or with the synth feeding the pitch detection directly
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::audio::detection::{
+    feed_detector, DetectionSettings, ListenOutcome, NoteDetector, OctaveMatching, PitchReading,
+};
+use cpal::traits::DeviceTrait;
+use rodio::{OutputStream, OutputStreamHandle};
+
+use crate::audio::devices::{self, DeviceChoice, DeviceChoices};
+use crate::audio::synth::{self, Timbre};
+use crate::audio::{listen, play_sample};
+use crate::music::{Note, Presentation, SimpleNote};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoundEffect {
+    Ding,
+    Wrong,
+}
+
+pub trait AudioBackend: Send + Sync {
+    /// Plays both notes one after the other or together, returns once they're over
+    fn play_notes(
+        &self,
+        n1: Note,
+        n2: Note,
+        presentation: Presentation,
+        note_length: Duration,
+    ) -> Result<(), String>;
+
+    /// Plays the notes together, returns once they're over
+    fn play_chord(&self, notes: &[Note], note_length: Duration) -> Result<(), String>;
+
+    /// Plays the chords one after the other, to set a key rather than to be sung back
+    fn play_cadence(&self, chords: &[Vec<Note>], chord_length: Duration) -> Result<(), String>;
+
+    /// Plays a single note, returns once it's over
+    fn play_note(&self, note: Note, note_length: Duration) -> Result<(), String>;
+
+    /// Returns right away, the effect plays in the background
+    fn play_effect(&self, effect: SoundEffect);
+
+    /// Returns as soon as the target is matched, or once the detection window is over. What's
+    /// heard in the meantime is streamed to `readings`
+    fn listen_for_note(
+        &self,
+        target_note: SimpleNote,
+        matching: OctaveMatching,
+        settings: DetectionSettings,
+        readings: Sender<PitchReading>,
+    ) -> Result<ListenOutcome, String>;
+
+    /// The name of the input device, to keep its calibration. `None` if there's nothing to
+    /// calibrate
+    fn input_device(&self) -> Option<String>;
+
+    /// Streams what's heard to `readings` until the receiver hangs up
+    fn track_pitch(
+        &self,
+        settings: DetectionSettings,
+        readings: Sender<PitchReading>,
+    ) -> Result<(), String>;
+}
+
+/// The chosen output device through rodio, the chosen input device through cpal, the default
+/// ones when none were chosen or the chosen ones are gone. Devices are opened again for every
+/// sound and every note listened for
+pub struct DeviceBackend {
+    sample_rate: u32,
+    timbre: Timbre,
+    devices: DeviceChoices,
+    /// falling back to a default device, for the user to see
+    warnings: Sender<String>,
+}
+
+impl DeviceBackend {
+    /// Fails if there is no output device. Without an input device the exercises are still
+    /// played, but nothing is heard. Chosen devices that are gone are replaced by the default
+    /// ones for the session, with a warning for each
+    pub fn open(
+        sample_rate: u32,
+        timbre: Timbre,
+        mut devices: DeviceChoices,
+        warnings: Sender<String>,
+    ) -> Result<Self, String> {
+        let (_, warning) = devices::open_output(devices.output.as_ref())?;
+        if let Some(warning) = warning {
+            devices.output = None;
+            warnings.send(warning).ok();
+        }
+        match devices::open_input(devices.input.as_ref()) {
+            Ok((_, None)) => {}
+            Ok((_, Some(warning))) => {
+                devices.input = None;
+                warnings.send(warning).ok();
+            }
+            Err(e) => {
+                warnings.send(format!("{e}, nothing will be heard")).ok();
+            }
+        }
+
+        Ok(DeviceBackend { sample_rate, timbre, devices, warnings })
+    }
+
+    /// A stream to the output device, for as long as it's kept
+    fn output(&self) -> Result<(OutputStream, OutputStreamHandle), String> {
+        open_output(self.devices.output.as_ref(), &self.warnings)
+    }
+
+    fn input(&self) -> Result<cpal::Device, String> {
+        let (device, warning) = devices::open_input(self.devices.input.as_ref())?;
+        if let Some(warning) = warning {
+            self.warnings.send(warning).ok();
+        }
+        Ok(device)
+    }
+}
+
+/// The device may have gone away since the backend was opened
+fn open_output(
+    choice: Option<&DeviceChoice>,
+    warnings: &Sender<String>,
+) -> Result<(OutputStream, OutputStreamHandle), String> {
+    let (stream, warning) = devices::open_output(choice)?;
+    if let Some(warning) = warning {
+        warnings.send(warning).ok();
+    }
+    Ok(stream)
+}
+
+impl AudioBackend for DeviceBackend {
+    fn play_notes(
+        &self,
+        n1: Note,
+        n2: Note,
+        presentation: Presentation,
+        note_length: Duration,
+    ) -> Result<(), String> {
+        let (_stream, output) = self.output()?;
+        synth::play_notes(
+            n1,
+            n2,
+            presentation,
+            note_length,
+            self.timbre,
+            &output,
+            self.sample_rate,
+        )
+    }
+
+    fn play_chord(&self, notes: &[Note], note_length: Duration) -> Result<(), String> {
+        let (_stream, output) = self.output()?;
+        synth::play_chords(
+            &[notes.to_vec()],
+            note_length,
+            self.timbre,
+            &output,
+            self.sample_rate,
+        )
+    }
+
+    fn play_cadence(&self, chords: &[Vec<Note>], chord_length: Duration) -> Result<(), String> {
+        let (_stream, output) = self.output()?;
+        synth::play_chords(chords, chord_length, self.timbre, &output, self.sample_rate)
+    }
+
+    fn play_note(&self, note: Note, note_length: Duration) -> Result<(), String> {
+        let (_stream, output) = self.output()?;
+        synth::play_chords(
+            &[vec![note]],
+            note_length,
+            self.timbre,
+            &output,
+            self.sample_rate,
+        )
+    }
+
+    fn play_effect(&self, effect: SoundEffect) {
+        let (path, length) = match effect {
+            SoundEffect::Ding => (play_sample::DING, Duration::from_millis(300)),
+            SoundEffect::Wrong => (play_sample::WRONG_SOUND_EFFECT, Duration::from_millis(3000)),
+        };
+        let output = self.devices.output.clone();
+        let warnings = self.warnings.clone();
+        std::thread::spawn(move || {
+            let played = open_output(output.as_ref(), &warnings)
+                .and_then(|(_stream, handle)| play_sample::play_sample(path, length, &handle));
+            if let Err(e) = played {
+                eprintln!("could not play {path}: {e}");
+            }
+        });
+    }
+
+    fn listen_for_note(
+        &self,
+        target_note: SimpleNote,
+        matching: OctaveMatching,
+        settings: DetectionSettings,
+        readings: Sender<PitchReading>,
+    ) -> Result<ListenOutcome, String> {
+        listen::listen_for_note(
+            target_note,
+            matching,
+            settings,
+            readings,
+            &self.input()?,
+            self.sample_rate,
+        )
+    }
+
+    fn input_device(&self) -> Option<String> {
+        self.input()
+            .and_then(|device| device.name().map_err(|e| e.to_string()))
+            .map_err(|e| eprintln!("could not name the input device: {e}"))
+            .ok()
+    }
+
+    fn track_pitch(
+        &self,
+        settings: DetectionSettings,
+        readings: Sender<PitchReading>,
+    ) -> Result<(), String> {
+        listen::track_pitch(settings, readings, &self.input()?, self.sample_rate)
+    }
+}
+
+/// Plays nothing and hears nothing, without waiting
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play_notes(&self, _: Note, _: Note, _: Presentation, _: Duration) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play_chord(&self, _: &[Note], _: Duration) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play_cadence(&self, _: &[Vec<Note>], _: Duration) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play_note(&self, _: Note, _: Duration) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play_effect(&self, _: SoundEffect) {}
+
+    fn listen_for_note(
+        &self,
+        target_note: SimpleNote,
+        matching: OctaveMatching,
+        settings: DetectionSettings,
+        _: Sender<PitchReading>,
+    ) -> Result<ListenOutcome, String> {
+        Ok(ListenOutcome {
+            target: target_note,
+            heard: None,
+            success: false,
+            elapsed: settings.window,
+            matching,
+        })
+    }
+
+    fn input_device(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns right away, there's nothing to tune
+    fn track_pitch(&self, _: DetectionSettings, _: Sender<PitchReading>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The synth output is kept in memory and fed to the pitch detection, one note per listen,
+/// as if the user sang back exactly what was played. Nothing waits on a clock
+pub struct LoopbackBackend {
+    sample_rate: u32,
+    timbre: Timbre,
+    played: Mutex<VecDeque<Vec<f32>>>,
+}
+
+impl LoopbackBackend {
+    pub fn new(sample_rate: u32, timbre: Timbre) -> Self {
+        LoopbackBackend { sample_rate, timbre, played: Mutex::new(VecDeque::new()) }
+    }
+}
+
+impl AudioBackend for LoopbackBackend {
+    /// A harmonic interval is heard one note at a time, the way it would be sung back
+    fn play_notes(
+        &self,
+        n1: Note,
+        n2: Note,
+        _: Presentation,
+        note_length: Duration,
+    ) -> Result<(), String> {
+        let notes = synth::render_notes(n1, n2, note_length, self.timbre, self.sample_rate);
+        self.played.lock().unwrap().extend(notes);
+        Ok(())
+    }
+
+    /// The whole chord is heard, the chord tone to sing has to stand out of it. A chord that
+    /// was never listened to, its quality being picked instead, is dropped by the next one
+    fn play_chord(&self, notes: &[Note], note_length: Duration) -> Result<(), String> {
+        let samples = synth::render_chords(
+            &[notes.to_vec()],
+            note_length,
+            self.timbre,
+            self.sample_rate,
+        );
+        let mut played = self.played.lock().unwrap();
+        played.clear();
+        played.push_back(samples);
+        Ok(())
+    }
+
+    /// Nobody sings a cadence back
+    fn play_cadence(&self, _: &[Vec<Note>], _: Duration) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play_note(&self, note: Note, note_length: Duration) -> Result<(), String> {
+        let samples =
+            synth::render_chords(&[vec![note]], note_length, self.timbre, self.sample_rate);
+        self.played.lock().unwrap().push_back(samples);
+        Ok(())
+    }
+
+    /// Sound effects aren't played back, they'd be heard instead of the next note
+    fn play_effect(&self, _: SoundEffect) {}
+
+    /// Silence once every played note has been heard
+    fn listen_for_note(
+        &self,
+        target_note: SimpleNote,
+        matching: OctaveMatching,
+        settings: DetectionSettings,
+        readings: Sender<PitchReading>,
+    ) -> Result<ListenOutcome, String> {
+        let samples = self.played.lock().unwrap().pop_front().unwrap_or_default();
+        let detector = NoteDetector::new(target_note, matching, settings, self.sample_rate)
+            .with_readings(readings);
+
+        Ok(feed_detector(detector, samples, self.sample_rate))
+    }
+
+    fn input_device(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns right away, nobody plays into the tuner
+    fn track_pitch(&self, _: DetectionSettings, _: Sender<PitchReading>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_hears_what_was_played() {
+        let backend = LoopbackBackend::new(44_100, Timbre::default());
+        let c4 = Note::parse_from_string("C4").unwrap();
+        let e4 = Note::parse_from_string("E4").unwrap();
+        let detection = DetectionSettings::default();
+        let (readings, _) = std::sync::mpsc::channel();
+
+        backend
+            .play_notes(c4, e4, Presentation::Melodic, Duration::from_millis(1000))
+            .unwrap();
+
+        let first = backend
+            .listen_for_note(
+                c4.to_simple(),
+                OctaveMatching::Tolerant,
+                detection,
+                readings.clone(),
+            )
+            .unwrap();
+        assert!(first.success);
+        let second = backend
+            .listen_for_note(
+                c4.to_simple(),
+                OctaveMatching::Tolerant,
+                detection,
+                readings.clone(),
+            )
+            .unwrap();
+        assert!(!second.success);
+        assert_eq!(second.heard.unwrap().note, e4.to_simple());
+        let third = backend
+            .listen_for_note(
+                c4.to_simple(),
+                OctaveMatching::Tolerant,
+                detection,
+                readings.clone(),
+            )
+            .unwrap();
+        assert_eq!(third.heard, None);
+    }
+
+    #[test]
+    fn null_hears_nothing() {
+        let outcome = NullBackend
+            .listen_for_note(
+                SimpleNote::new(60),
+                OctaveMatching::Tolerant,
+                DetectionSettings::default(),
+                std::sync::mpsc::channel().0,
+            )
+            .unwrap();
+
+        assert!(!outcome.success);
+        assert_eq!(outcome.heard, None);
+        assert_eq!(outcome.elapsed, Duration::from_millis(1500));
+    }
+}
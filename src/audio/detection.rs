@@ -0,0 +1,672 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use pitch_detection::detector::{mcleod::McLeodDetector, PitchDetector};
+
+use crate::music::SimpleNote;
+
+pub type CentDeviation = i8;
+
+/// How the pitch detection listens, a quiet flute and a loud trumpet need different thresholds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DetectionSettings {
+    /// sum of the squared samples of a buffer, below which it's silence
+    pub power_threshold: f32,
+    /// from 0 to 1, how clearly pitched a buffer must be for its pitch to count
+    pub clarity_threshold: f32,
+    /// how far from the target a matching note may be
+    pub cent_deviation_threshold: CentDeviation,
+    /// samples per detection, longer buffers hear lower notes but react later
+    pub buffer_size: usize,
+    /// how long to listen for a note before it's a miss
+    pub window: Duration,
+}
+
+impl DetectionSettings {
+    pub const DEFAULT: DetectionSettings = DetectionSettings {
+        power_threshold: 5.0,
+        clarity_threshold: 0.7,
+        cent_deviation_threshold: 20,
+        buffer_size: 1024,
+        window: Duration::from_millis(1500),
+    };
+}
+
+impl Default for DetectionSettings {
+    fn default() -> Self {
+        DetectionSettings::DEFAULT
+    }
+}
+
+/// Which octaves of the target count
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OctaveMatching {
+    /// only the target itself
+    Strict,
+    /// any octave, how far off it was being reported
+    #[default]
+    Tolerant,
+    /// only the target as sounded by a transposing instrument, that many semitones away from
+    /// the written note, e.g. -12 for a guitar
+    Transposed(i8),
+}
+
+/// Everything that was heard while listening for a note
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ListenOutcome {
+    pub target: SimpleNote,
+    /// the note that matched the target, or on a miss the note that was heard the most
+    pub heard: Option<HeardNote>,
+    pub success: bool,
+    /// until the target was matched, or the whole detection window on a miss
+    pub elapsed: Duration,
+    /// what counted as the target
+    pub matching: OctaveMatching,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HeardNote {
+    pub note: SimpleNote,
+    pub frequency: f64,
+    /// from `note`, negative means flat
+    pub cent_deviation: CentDeviation,
+}
+
+/// What was heard in one detection buffer, streamed to the UI while listening
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PitchReading {
+    /// since listening started, up to the end of the buffer
+    pub at: Duration,
+    /// of the buffer, as compared with the power threshold
+    pub power: f32,
+    /// of the pitch that was heard, 0 if none
+    pub clarity: f32,
+    /// `None` for silence or a sound without a clear pitch
+    pub heard: Option<HeardNote>,
+}
+
+/// Runs McLeod pitch detection over a signal one buffer at a time
+///
+/// Time is counted in samples, so feeding it from a microphone or from a file gives the same
+/// readings for the same signal
+pub struct PitchTracker {
+    settings: DetectionSettings,
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    n_samples: u64,
+}
+
+impl PitchTracker {
+    pub fn new(settings: DetectionSettings, sample_rate: u32) -> Self {
+        PitchTracker {
+            settings,
+            sample_rate,
+            buffer: Vec::with_capacity(2 * settings.buffer_size),
+            n_samples: 0,
+        }
+    }
+
+    /// A reading for every detection buffer the samples complete
+    pub fn push(&mut self, data: &[f32]) -> Vec<PitchReading> {
+        self.buffer.extend_from_slice(data);
+
+        let size = self.settings.buffer_size;
+        let mut readings = Vec::new();
+        while self.buffer.len() >= size {
+            self.n_samples += size as u64;
+            let samples = &self.buffer[0..size];
+            // the detector isn't `Send`, so it can't be kept around in the audio callback
+            let mut detector = McLeodDetector::new(size, size / 2);
+            let pitch = detector.get_pitch(
+                samples,
+                self.sample_rate as usize,
+                self.settings.power_threshold,
+                self.settings.clarity_threshold,
+            );
+            let power = samples.iter().map(|sample| sample * sample).sum();
+            self.buffer.drain(0..size);
+
+            readings.push(PitchReading {
+                at: self.elapsed(),
+                power,
+                clarity: pitch.as_ref().map_or(0.0, |pitch| pitch.clarity),
+                heard: pitch.and_then(|pitch| HeardNote::at(pitch.frequency.into())),
+            });
+        }
+
+        readings
+    }
+
+    /// Audio time covered by the samples that went through detection
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.n_samples as f64 / self.sample_rate as f64)
+    }
+}
+
+/// The device independent half of listening for a note
+///
+/// Every pitch the tracker hears is matched against the target, and can be streamed as it's
+/// heard with `with_readings`
+pub struct NoteDetector {
+    target: SimpleNote,
+    matching: OctaveMatching,
+    cent_deviation_threshold: CentDeviation,
+    window: Duration,
+    tracker: PitchTracker,
+    readings: Option<Sender<PitchReading>>,
+    // how many detection buffers each note was heard in
+    heard_notes: Vec<(HeardNote, u32)>,
+    outcome: Option<ListenOutcome>,
+}
+
+impl NoteDetector {
+    pub fn new(
+        target: SimpleNote,
+        matching: OctaveMatching,
+        settings: DetectionSettings,
+        sample_rate: u32,
+    ) -> Self {
+        NoteDetector {
+            target,
+            matching,
+            cent_deviation_threshold: settings.cent_deviation_threshold,
+            window: settings.window,
+            tracker: PitchTracker::new(settings, sample_rate),
+            readings: None,
+            heard_notes: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    /// Sends every reading until the target is matched, nobody listening is fine
+    pub fn with_readings(self, readings: Sender<PitchReading>) -> Self {
+        NoteDetector { readings: Some(readings), ..self }
+    }
+
+    /// Returns the outcome once, as soon as the target is matched. Later samples are ignored
+    pub fn push(&mut self, data: &[f32]) -> Option<ListenOutcome> {
+        if self.outcome.is_some() {
+            return None;
+        }
+
+        for reading in self.tracker.push(data) {
+            if let Some(readings) = &self.readings {
+                readings.send(reading).ok();
+            }
+            if let Some(heard) = reading.heard {
+                if let Some(outcome) = self.hear(heard, reading.at) {
+                    self.outcome = Some(outcome);
+                    return self.outcome;
+                }
+            }
+        }
+
+        None
+    }
+
+    fn hear(&mut self, heard: HeardNote, at: Duration) -> Option<ListenOutcome> {
+        let note = heard.note;
+
+        if heard.cent_deviation.abs() < self.cent_deviation_threshold
+            && self.matching.matches(note, self.target)
+        {
+            return Some(ListenOutcome {
+                target: self.target,
+                heard: Some(heard),
+                success: true,
+                elapsed: at,
+                matching: self.matching,
+            });
+        }
+
+        match self.heard_notes.iter_mut().find(|(h, _)| h.note == note) {
+            Some(entry) => *entry = (heard, entry.1 + 1),
+            None => self.heard_notes.push((heard, 1)),
+        }
+        None
+    }
+
+    /// Audio time covered by the samples that went through detection
+    pub fn elapsed(&self) -> Duration {
+        self.tracker.elapsed()
+    }
+
+    /// The outcome once the window is over, whether the target was matched or not
+    pub fn finish(&self) -> ListenOutcome {
+        if let Some(outcome) = self.outcome {
+            return outcome;
+        }
+
+        ListenOutcome {
+            target: self.target,
+            heard: self
+                .heard_notes
+                .iter()
+                .max_by_key(|(_, count)| *count)
+                .map(|(heard, _)| *heard),
+            success: false,
+            elapsed: self.window,
+            matching: self.matching,
+        }
+    }
+}
+
+impl HeardNote {
+    /// `None` for a pitch out of the range of notes, as if nothing was heard
+    pub fn at(frequency: f64) -> Option<Self> {
+        let (note, cent_deviation) = closest_note(frequency)?;
+        Some(HeardNote { note, frequency, cent_deviation })
+    }
+
+    /// Cents above the expected note in the closest octave, negative means flat
+    pub fn cents_off(&self, expected: SimpleNote) -> i32 {
+        let semitones = (self.note.get_i8() - expected.get_i8() + 6).rem_euclid(12) - 6;
+        100 * semitones as i32 + self.cent_deviation as i32
+    }
+}
+
+impl ListenOutcome {
+    /// How many octaves above the target, as it should sound, the heard note was. `None` if it
+    /// wasn't the target in any octave
+    pub fn octaves_off(&self) -> Option<i8> {
+        let heard = self.heard?.note.get_i8();
+        let expected = self.target.shift(self.matching.transposition()).get_i8();
+        ((heard - expected) % 12 == 0).then_some((heard - expected) / 12)
+    }
+}
+
+impl OctaveMatching {
+    /// Whether the heard note counts as the target
+    pub fn matches(&self, heard: SimpleNote, target: SimpleNote) -> bool {
+        match self {
+            OctaveMatching::Strict => heard == target,
+            OctaveMatching::Tolerant => are_octaves_away(heard, target),
+            OctaveMatching::Transposed(semitones) => heard == target.shift(*semitones),
+        }
+    }
+
+    /// From the written note to the sounding one
+    pub fn transposition(&self) -> i8 {
+        match self {
+            OctaveMatching::Transposed(semitones) => *semitones,
+            _ => 0,
+        }
+    }
+
+    /// "strict", "tolerant", or "transposed:" followed by the semitones, e.g. "transposed:-2"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        let error = || {
+            format!("invalid octaves: {string} (expected strict, tolerant or transposed:SEMITONES)")
+        };
+        match string.split_once(':') {
+            None if string == "strict" => Ok(OctaveMatching::Strict),
+            None if string == "tolerant" => Ok(OctaveMatching::Tolerant),
+            Some(("transposed", semitones)) => semitones
+                .parse()
+                .ok()
+                .filter(|semitones: &i8| semitones.abs() <= 36)
+                .map(OctaveMatching::Transposed)
+                .ok_or_else(error),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl fmt::Display for OctaveMatching {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OctaveMatching::Strict => write!(f, "strict"),
+            OctaveMatching::Tolerant => write!(f, "tolerant"),
+            OctaveMatching::Transposed(semitones) => write!(f, "transposed:{semitones}"),
+        }
+    }
+}
+
+/// Offline counterpart of `listen_for_note`, for any mono signal
+pub fn detect_note(
+    samples: impl IntoIterator<Item = f32>,
+    target_note: SimpleNote,
+    matching: OctaveMatching,
+    settings: DetectionSettings,
+    sample_rate: u32,
+) -> ListenOutcome {
+    let detector = NoteDetector::new(target_note, matching, settings, sample_rate);
+    feed_detector(detector, samples, sample_rate)
+}
+
+/// Runs the samples through the detector the way `listen_for_note` would, up to its window
+pub fn feed_detector(
+    mut detector: NoteDetector,
+    samples: impl IntoIterator<Item = f32>,
+    sample_rate: u32,
+) -> ListenOutcome {
+    // roughly what an audio callback gets at a time
+    const CHUNK_SIZE: usize = 512;
+
+    let max_samples = (detector.window.as_secs_f64() * sample_rate as f64) as usize;
+    let mut samples = samples.into_iter().take(max_samples);
+    let mut chunk: Vec<f32> = Vec::with_capacity(CHUNK_SIZE);
+
+    loop {
+        chunk.clear();
+        chunk.extend(samples.by_ref().take(CHUNK_SIZE));
+        if chunk.is_empty() {
+            break;
+        }
+        if let Some(outcome) = detector.push(&chunk) {
+            return outcome;
+        }
+    }
+
+    detector.finish()
+}
+
+/// Offline counterpart of `listen_for_note` for a WAV file, several channels are mixed down
+pub fn detect_note_in_wav(
+    path: impl AsRef<Path>,
+    target_note: SimpleNote,
+    matching: OctaveMatching,
+    settings: DetectionSettings,
+) -> Result<ListenOutcome, String> {
+    let path = path.as_ref();
+    let mut reader =
+        hound::WavReader::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(|e| format!("{}: {e}", path.display()))?;
+
+    let channels = spec.channels as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32);
+
+    Ok(detect_note(
+        mono,
+        target_note,
+        matching,
+        settings,
+        spec.sample_rate,
+    ))
+}
+
+/// `None` outside of MIDI notes 0 to 127, i.e. below about 8 Hz or above about 12.5 kHz, which
+/// a WAV file can easily reach
+pub fn closest_note(f: f64) -> Option<(SimpleNote, CentDeviation)> {
+    let cents_from_a4 = cents_between(440.0, f);
+    let cents_from_c_min_1 = cents_from_a4.saturating_add(69 * 100);
+
+    // half a semitone up rounds to the note above
+    let semitones_from_c_min_1 = cents_from_c_min_1.saturating_add(50).div_euclid(100);
+    let deviation = cents_from_c_min_1 - 100 * semitones_from_c_min_1;
+
+    let note: i8 = semitones_from_c_min_1.try_into().ok()?;
+    match note >= 0 {
+        true => Some((SimpleNote::new(note), deviation.try_into().ok()?)),
+        false => None,
+    }
+}
+
+fn are_octaves_away(n1: SimpleNote, n2: SimpleNote) -> bool {
+    (n1.get_i8() - n2.get_i8()) % 12 == 0
+}
+
+fn cents_between(f0: f64, f: f64) -> i32 {
+    (1200.0 * f64::log2(f / f0)).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::synth::synth::{Oscillator, Wavetable};
+
+    const SAMPLE_RATE: u32 = 44_100;
+    const DETECTION: DetectionSettings = DetectionSettings::DEFAULT;
+    const WINDOW: Duration = DETECTION.window;
+
+    const A4: SimpleNote = SimpleNote { data: 69 };
+    const G4: SimpleNote = SimpleNote { data: 67 };
+    const TOLERANT: OctaveMatching = OctaveMatching::Tolerant;
+
+    fn sine(frequency: f64) -> impl Iterator<Item = f32> {
+        (0..).map(move |i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            (0.5 * (2.0 * std::f64::consts::PI * frequency * t).sin()) as f32
+        })
+    }
+
+    fn cents_up(frequency: f64, cents: f64) -> f64 {
+        frequency * 2.0_f64.powf(cents / 1200.0)
+    }
+
+    #[test]
+    fn closest_note_and_deviation() {
+        assert_eq!(closest_note(440.0), Some((A4, 0)));
+        assert_eq!(closest_note(cents_up(440.0, 30.0)), Some((A4, 30)));
+        assert_eq!(closest_note(cents_up(440.0, -30.0)), Some((A4, -30)));
+        assert_eq!(
+            closest_note(cents_up(440.0, 70.0)),
+            Some((A4.shift(1), -30))
+        );
+        assert_eq!(
+            closest_note(cents_up(440.0, 50.0)),
+            Some((A4.shift(1), -50))
+        );
+
+        // G9, the highest MIDI note, and past it
+        assert_eq!(closest_note(12_543.85), Some((SimpleNote::new(127), 0)));
+        assert_eq!(closest_note(13_000.0), None);
+        assert_eq!(closest_note(0.0), None);
+    }
+
+    #[test]
+    fn pitches_above_the_notes_are_nothing_heard() {
+        let outcome = detect_note(sine(14_000.0), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(!outcome.success);
+        assert_eq!(outcome.heard, None);
+    }
+
+    #[test]
+    fn in_tune_sine_is_matched() {
+        let outcome = detect_note(sine(440.0), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(outcome.success);
+        let heard = outcome.heard.unwrap();
+        assert_eq!(heard.note, A4);
+        // McLeod over a single buffer is only good to a few cents
+        assert!(heard.cent_deviation.abs() < DETECTION.cent_deviation_threshold / 2);
+        assert!(outcome.elapsed < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn any_octave_is_matched() {
+        let outcome = detect_note(sine(220.0), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(outcome.success);
+        assert_eq!(outcome.heard.unwrap().note, A4.shift(-12));
+    }
+
+    #[test]
+    fn octaves_are_checked_unless_tolerant() {
+        let a3 = || sine(220.0);
+        let strict = detect_note(a3(), A4, OctaveMatching::Strict, DETECTION, SAMPLE_RATE);
+        assert!(!strict.success);
+        assert_eq!(strict.octaves_off(), Some(-1));
+
+        let tolerant = detect_note(a3(), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+        assert_eq!(tolerant.octaves_off(), Some(-1));
+
+        // a guitar sounds an octave below the written note
+        let guitar = OctaveMatching::Transposed(-12);
+        let transposed = detect_note(a3(), A4, guitar, DETECTION, SAMPLE_RATE);
+        assert!(transposed.success);
+        assert_eq!(transposed.octaves_off(), Some(0));
+        assert_eq!(OctaveMatching::parse("transposed:-12"), Ok(guitar));
+    }
+
+    #[test]
+    fn wrong_note_is_reported() {
+        let outcome = detect_note(sine(466.16), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(!outcome.success);
+        assert_eq!(outcome.heard.unwrap().note, A4.shift(1));
+        assert_eq!(outcome.elapsed, WINDOW);
+    }
+
+    #[test]
+    fn out_of_tune_note_is_a_miss() {
+        let outcome = detect_note(
+            sine(cents_up(440.0, -40.0)),
+            A4,
+            TOLERANT,
+            DETECTION,
+            SAMPLE_RATE,
+        );
+
+        assert!(!outcome.success);
+        let heard = outcome.heard.unwrap();
+        assert_eq!(heard.note, A4);
+        assert!(heard.cent_deviation <= -DETECTION.cent_deviation_threshold);
+    }
+
+    #[test]
+    fn silence_is_a_miss_with_nothing_heard() {
+        let outcome = detect_note(std::iter::repeat(0.0), A4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(!outcome.success);
+        assert_eq!(outcome.heard, None);
+    }
+
+    #[test]
+    fn late_note_is_matched_late() {
+        let silence = std::iter::repeat_n(0.0, SAMPLE_RATE as usize / 2);
+        let outcome = detect_note(
+            silence.chain(sine(440.0)),
+            A4,
+            TOLERANT,
+            DETECTION,
+            SAMPLE_RATE,
+        );
+
+        assert!(outcome.success);
+        assert!(outcome.elapsed >= Duration::from_millis(500));
+        assert!(outcome.elapsed < Duration::from_millis(600));
+    }
+
+    #[test]
+    fn readings_are_streamed_until_the_match() {
+        let (readings_tx, readings_rx) = std::sync::mpsc::channel();
+        let silence = std::iter::repeat_n(0.0, SAMPLE_RATE as usize / 4);
+        let detector =
+            NoteDetector::new(A4, TOLERANT, DETECTION, SAMPLE_RATE).with_readings(readings_tx);
+
+        let outcome = feed_detector(detector, silence.chain(sine(440.0)), SAMPLE_RATE);
+        let readings: Vec<PitchReading> = readings_rx.try_iter().collect();
+
+        assert!(readings[0].heard.is_none());
+        let last = readings.last().unwrap();
+        assert_eq!(last.at, outcome.elapsed);
+        assert_eq!(last.heard, outcome.heard);
+        assert_eq!(
+            last.heard.unwrap().cents_off(A4.shift(-12)),
+            last.heard.unwrap().cent_deviation as i32
+        );
+        assert!(readings.windows(2).all(|pair| pair[0].at < pair[1].at));
+    }
+
+    #[test]
+    fn note_after_the_window_is_a_miss() {
+        let silence = std::iter::repeat_n(0.0, 2 * SAMPLE_RATE as usize);
+        let outcome = detect_note(
+            silence.chain(sine(440.0)),
+            A4,
+            TOLERANT,
+            DETECTION,
+            SAMPLE_RATE,
+        );
+
+        assert!(!outcome.success);
+    }
+
+    #[test]
+    fn synth_output_is_matched() {
+        let mut oscillator = Oscillator::new(SAMPLE_RATE, Wavetable::square8());
+        oscillator.set_frequency(392.0);
+
+        let outcome = detect_note(oscillator, G4, TOLERANT, DETECTION, SAMPLE_RATE);
+
+        assert!(outcome.success);
+        assert_eq!(outcome.heard.unwrap().note, G4);
+    }
+
+    #[test]
+    fn wav_file_gives_the_same_outcome_as_the_samples() {
+        let path = std::env::temp_dir().join(format!("detection_test_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let samples: Vec<f32> = sine(cents_up(392.0, 8.0))
+            .take(SAMPLE_RATE as usize)
+            .collect();
+
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &sample in &samples {
+            for _ in 0..spec.channels {
+                writer
+                    .write_sample((sample * i16::MAX as f32) as i16)
+                    .unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+
+        let from_wav = detect_note_in_wav(&path, G4, TOLERANT, DETECTION).unwrap();
+        let from_samples = detect_note(samples, G4, TOLERANT, DETECTION, SAMPLE_RATE);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(from_wav.success);
+        assert_eq!(
+            from_wav.heard.unwrap().note,
+            from_samples.heard.unwrap().note
+        );
+        assert_eq!(
+            from_wav.heard.unwrap().cent_deviation,
+            from_samples.heard.unwrap().cent_deviation
+        );
+        assert_eq!(from_wav.elapsed, from_samples.elapsed);
+    }
+
+    /// The app's own sound effects double as recorded fixtures: the ding is a G6, the buzzer
+    /// has no clear pitch
+    #[test]
+    fn recorded_fixtures() {
+        const DING: &str = "src/assets/audio/ding.wav";
+        const BUZZER: &str = "src/assets/audio/wrong.wav";
+
+        let ding = detect_note_in_wav(DING, G4, TOLERANT, DETECTION).unwrap();
+        assert!(ding.success);
+        assert_eq!(ding.heard.unwrap().note, G4.shift(24));
+
+        let ding = detect_note_in_wav(DING, A4, TOLERANT, DETECTION).unwrap();
+        assert!(!ding.success);
+        assert_eq!(ding.heard.unwrap().note, G4.shift(24));
+
+        let buzzer = detect_note_in_wav(BUZZER, A4, TOLERANT, DETECTION).unwrap();
+        assert!(!buzzer.success);
+        assert_eq!(buzzer.heard, None);
+    }
+}
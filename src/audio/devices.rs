@@ -0,0 +1,297 @@
+//! Which sound devices to play to and listen from
+//!
+//! Devices are picked by name, on any of the hosts cpal knows about on this platform (ALSA or
+//! JACK on Linux, WASAPI or ASIO on Windows, CoreAudio on macOS). A device that isn't there
+//! anymore falls back to the default one of its kind, with a warning for the user, rather than
+//! failing.
+//!
+//! Input goes through cpal directly, output through rodio and the version of cpal it depends on,
+//! hence the two of everything
+
+use std::fmt;
+use std::path::Path;
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::traits::{DeviceTrait as _, HostTrait as _};
+use rodio::{OutputStream, OutputStreamHandle};
+
+use crate::history::{escape, parse_flat_object, Value};
+
+/// The devices picked in the menu, next to the practice history
+pub const DEVICES_FILE: &str = "devices.json";
+
+/// A device picked by the user, by the name of its host and its own
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceChoice {
+    pub host: String,
+    pub name: String,
+}
+
+/// An input and an output device, the default ones when `None`. Either given on the command line
+/// (`Settings::devices`) or picked in the device menu, only the latter being saved to
+/// `DEVICES_FILE` when they're picked
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceChoices {
+    pub input: Option<DeviceChoice>,
+    pub output: Option<DeviceChoice>,
+}
+
+impl DeviceChoice {
+    /// "NAME" for a device of the default host, or "HOST/NAME", e.g. "JACK/system"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        if string.is_empty() {
+            return Err(String::from(
+                "expected a device name, see `interval_trainer devices`",
+            ));
+        }
+        let hosts: Vec<&str> = cpal::available_hosts().iter().map(|id| id.name()).collect();
+
+        Ok(match string.split_once('/') {
+            Some((host, name)) if hosts.contains(&host) => {
+                DeviceChoice { host: host.to_string(), name: name.to_string() }
+            }
+            _ => DeviceChoice {
+                host: cpal::default_host().id().name().to_string(),
+                name: string.to_string(),
+            },
+        })
+    }
+}
+
+impl fmt::Display for DeviceChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host == cpal::default_host().id().name() {
+            true => write!(f, "{}", self.name),
+            false => write!(f, "{}/{}", self.host, self.name),
+        }
+    }
+}
+
+/// Every input device on every host
+pub fn input_devices() -> Vec<DeviceChoice> {
+    let mut choices = Vec::new();
+    for id in cpal::available_hosts() {
+        let Ok(devices) = cpal::host_from_id(id)
+            .and_then(|host| host.input_devices().map_err(|_| cpal::HostUnavailable))
+        else {
+            continue;
+        };
+        choices.extend(devices.filter_map(|device| {
+            let name = device.name().ok()?;
+            Some(DeviceChoice { host: id.name().to_string(), name })
+        }));
+    }
+    choices
+}
+
+/// Every output device on every host
+pub fn output_devices() -> Vec<DeviceChoice> {
+    let mut choices = Vec::new();
+    for id in rodio::cpal::available_hosts() {
+        let Ok(devices) = rodio::cpal::host_from_id(id).and_then(|host| {
+            host.output_devices()
+                .map_err(|_| rodio::cpal::HostUnavailable)
+        }) else {
+            continue;
+        };
+        choices.extend(devices.filter_map(|device| {
+            let name = device.name().ok()?;
+            Some(DeviceChoice { host: id.name().to_string(), name })
+        }));
+    }
+    choices
+}
+
+/// For the `devices` command
+pub fn describe() -> String {
+    let list = |devices: Vec<DeviceChoice>| match devices.is_empty() {
+        true => String::from("  (none)\n"),
+        false => devices.iter().map(|d| format!("  {d}\n")).collect(),
+    };
+    format!(
+        "Input devices:\n{}Output devices:\n{}",
+        list(input_devices()),
+        list(output_devices())
+    )
+}
+
+/// The chosen input device, or the default one if it's gone along with a warning saying so
+pub fn open_input(choice: Option<&DeviceChoice>) -> Result<(cpal::Device, Option<String>), String> {
+    let mut warning = None;
+    if let Some(choice) = choice {
+        let host = cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name() == choice.host)
+            .and_then(|id| cpal::host_from_id(id).ok());
+        let device = host
+            .and_then(|host| host.input_devices().ok())
+            .and_then(|mut devices| {
+                devices.find(|device| device.name().ok().as_ref() == Some(&choice.name))
+            });
+        match device {
+            Some(device) => return Ok((device, None)),
+            None => {
+                warning = Some(format!(
+                    "input device {choice} not found, using the default one"
+                ))
+            }
+        }
+    }
+
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or(String::from("no input device available"))?;
+    Ok((device, warning))
+}
+
+/// A stream to the chosen output device, or to the default one if it's gone along with a warning
+/// saying so
+pub fn open_output(
+    choice: Option<&DeviceChoice>,
+) -> Result<((OutputStream, OutputStreamHandle), Option<String>), String> {
+    let mut warning = None;
+    if let Some(choice) = choice {
+        let host = rodio::cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name() == choice.host)
+            .and_then(|id| rodio::cpal::host_from_id(id).ok());
+        let device = host
+            .and_then(|host| host.output_devices().ok())
+            .and_then(|mut devices| {
+                devices.find(|device| device.name().ok().as_ref() == Some(&choice.name))
+            });
+        match device.map(|device| OutputStream::try_from_device(&device)) {
+            Some(Ok(stream)) => return Ok((stream, None)),
+            Some(Err(e)) => {
+                warning = Some(format!(
+                    "could not open output device {choice}: {e}, using the default one"
+                ))
+            }
+            None => {
+                warning = Some(format!(
+                    "output device {choice} not found, using the default one"
+                ))
+            }
+        }
+    }
+
+    let stream = OutputStream::try_default().map_err(|e| format!("no output device: {e}"))?;
+    Ok((stream, warning))
+}
+
+impl DeviceChoices {
+    /// The choices saved last time, none if they never were
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let line = match std::fs::read_to_string(path) {
+            Ok(line) => line,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+
+        let fields = parse_flat_object(&line).map_err(|e| format!("{}: {e}", path.display()))?;
+        let choice = |key: &str| -> Result<Option<DeviceChoice>, String> {
+            let field = |suffix: &str| {
+                fields
+                    .iter()
+                    .find(|(k, _)| *k == format!("{key}_{suffix}"))
+                    .map(|(_, v)| v)
+            };
+            match (field("host"), field("name")) {
+                (Some(Value::String(host)), Some(Value::String(name))) => Ok(Some(DeviceChoice {
+                    host: host.clone(),
+                    name: name.clone(),
+                })),
+                (None | Some(Value::Null), None | Some(Value::Null)) => Ok(None),
+                _ => Err(format!("{}: invalid {key} device", path.display())),
+            }
+        };
+
+        Ok(DeviceChoices { input: choice("input")?, output: choice("output")? })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, self.to_json() + "\n").map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn to_json(&self) -> String {
+        let field = |choice: &Option<DeviceChoice>, key: &str| match choice {
+            Some(choice) => format!(
+                "\"{key}_host\":\"{}\",\"{key}_name\":\"{}\"",
+                escape(&choice.host),
+                escape(&choice.name)
+            ),
+            None => format!("\"{key}_host\":null,\"{key}_name\":null"),
+        };
+        format!(
+            "{{{},{}}}",
+            field(&self.input, "input"),
+            field(&self.output, "output")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_are_named_with_or_without_their_host() {
+        let default_host = cpal::default_host().id().name();
+        let mic = DeviceChoice::parse("USB Mic").unwrap();
+        assert_eq!(mic.host, default_host);
+        assert_eq!(
+            DeviceChoice::parse(&format!("{default_host}/USB Mic")),
+            Ok(mic.clone())
+        );
+        assert_eq!(mic.to_string(), "USB Mic");
+
+        // not a host, part of the name
+        let odd = DeviceChoice::parse("nowhere/USB Mic").unwrap();
+        assert_eq!(odd.name, "nowhere/USB Mic");
+        assert!(DeviceChoice::parse("").is_err());
+    }
+
+    #[test]
+    fn choices_are_remembered() {
+        let path = std::env::temp_dir().join(format!("devices_test_{}.json", std::process::id()));
+        assert_eq!(
+            DeviceChoices::load(&path).unwrap(),
+            DeviceChoices::default()
+        );
+
+        let choices = DeviceChoices {
+            input: Some(DeviceChoice {
+                host: String::from("ALSA"),
+                name: String::from("hw:CARD=\"USB\""),
+            }),
+            output: None,
+        };
+        choices.save(&path).unwrap();
+        assert_eq!(DeviceChoices::load(&path).unwrap(), choices);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_devices_fall_back_to_the_default_one() {
+        let gone = DeviceChoice { host: String::from("ALSA"), name: String::from("unplugged") };
+        let default = cpal::default_host()
+            .default_input_device()
+            .map(|d| d.name().unwrap());
+
+        match open_input(Some(&gone)) {
+            Ok((device, warning)) => {
+                assert_eq!(Some(device.name().unwrap()), default);
+                assert_eq!(
+                    warning,
+                    Some(format!(
+                        "input device {gone} not found, using the default one"
+                    ))
+                );
+            }
+            Err(_) => assert_eq!(default, None),
+        }
+    }
+}
@@ -1,156 +1,111 @@
-use std::sync::mpsc::Sender;
-use std::time::{Duration, Instant};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
 
 use cpal::{
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, Host, StreamConfig,
+    traits::{DeviceTrait, StreamTrait},
+    Stream, StreamConfig,
 };
 
+use crate::audio::detection::{DetectionSettings, NoteDetector, PitchTracker};
 use crate::music::SimpleNote;
-use pitch_detection::detector::{mcleod::McLeodDetector, PitchDetector};
-use std::sync::{atomic::AtomicU64, atomic::Ordering, Arc};
 
-pub fn listen_for_note_in_thread(
-    target_note: SimpleNote,
-    detection_duration: Duration,
-    sample_rate: u16,
-    signal: Sender<bool>,
-) {
-    std::thread::spawn(move || {
-        match listen_for_note(target_note, detection_duration, sample_rate) {
-            Some(_) => signal.send(true),
-            None => signal.send(false),
-        }
-    });
-}
-
-pub type CentDeviation = i8;
+pub use crate::audio::detection::{CentDeviation, ListenOutcome, OctaveMatching, PitchReading};
 
+/// Every reading is also sent to `readings` until the target is matched. Fails if the input
+/// device goes away while listening
 pub fn listen_for_note(
     target_note: SimpleNote,
-    detection_duration: Duration,
-    sample_rate: u16,
-) -> Option<CentDeviation> {
-    let (_host, input_device) = setup_input_device().unwrap();
-    let config = StreamConfig {
-        channels: 1,
-        sample_rate: cpal::SampleRate(sample_rate.into()),
-        buffer_size: cpal::BufferSize::Default,
-    };
-
-    const DETECTION_BUFFER_SIZE: usize = 1024;
-    const PADDING: usize = DETECTION_BUFFER_SIZE / 2;
-    const POWER_THRESHOLD: f32 = 5.0;
-    const CLARITY_THRESHOLD: f32 = 0.7;
-    const CENT_DEVIATION_THRESHOLD: CentDeviation = 20;
-
-    let audio_thread_freq = Arc::new(AtomicU64::new(0));
-    let ui_thread_freq = audio_thread_freq.clone();
-    let mut detection_buffer: Vec<f32> = Vec::new();
-
-    let input_callback = move |data: &[f32], _: &cpal::InputCallbackInfo| {
-        if detection_buffer.len() >= DETECTION_BUFFER_SIZE {
-            // buffer is ready to try pitch detection
-            let mut detector = McLeodDetector::new(DETECTION_BUFFER_SIZE, PADDING);
-            if let Some(pitch) = detector.get_pitch(
-                &detection_buffer[0..DETECTION_BUFFER_SIZE],
-                sample_rate.into(),
-                POWER_THRESHOLD,
-                CLARITY_THRESHOLD,
-            ) {
-                audio_thread_freq.store(
-                    Into::<f64>::into(pitch.frequency).to_bits(),
-                    Ordering::Relaxed,
-                );
-            }
-            detection_buffer.clear();
-        } else {
-            // detection buffer isn't full, use this callback to append a callback buffer
-            detection_buffer.extend_from_slice(data);
+    matching: OctaveMatching,
+    settings: DetectionSettings,
+    readings: Sender<PitchReading>,
+    input: &cpal::Device,
+    sample_rate: u32,
+) -> Result<ListenOutcome, String> {
+    let audio_thread_detector = Arc::new(Mutex::new(
+        NoteDetector::new(target_note, matching, settings, sample_rate).with_readings(readings),
+    ));
+    let listening_thread_detector = audio_thread_detector.clone();
+    let (success_tx, success_rx) = mpsc::channel();
+    let error_tx = success_tx.clone();
+
+    let input_callback = move |data: &[f32]| {
+        let Ok(mut detector) = audio_thread_detector.lock() else {
+            return;
+        };
+        if let Some(outcome) = detector.push(data) {
+            success_tx.send(Ok(outcome)).ok();
         }
     };
 
-    let stream = input_device
-        .build_input_stream::<f32, _, _>(
-            &config,
-            input_callback,
-            |e| eprintln!("An error has occured on the audio thread: {e}"),
-            None,
-        )
-        .unwrap();
-
-    const UPDATE_FPS: u8 = 10;
-    let start = Instant::now();
+    let stream = open_input_stream(input, sample_rate, input_callback, move |e| {
+        error_tx
+            .send(Err(format!("the input device stopped: {e}")))
+            .ok();
+    })?;
+    stream.play().map_err(|e| e.to_string())?;
+    let outcome = match success_rx.recv_timeout(settings.window) {
+        Ok(outcome) => outcome,
+        Err(_) => listening_thread_detector
+            .lock()
+            .map(|detector| detector.finish())
+            .map_err(|_| String::from("the audio thread panicked")),
+    };
+    // the device may be gone already
+    stream.pause().ok();
 
-    stream.play().unwrap();
-    while Instant::now().duration_since(start) < detection_duration {
-        let tick_start = Instant::now();
+    outcome
+}
 
-        let detected_pitch = f64::from_bits(ui_thread_freq.load(Ordering::Relaxed));
-        if let Some((note, deviation)) = get_note(detected_pitch, CENT_DEVIATION_THRESHOLD) {
-            if are_octaves_away(note, target_note) {
-                stream.pause().unwrap();
-                return Some(deviation);
+/// Sends a reading for every detection buffer until the receiver hangs up, for the tuner
+pub fn track_pitch(
+    settings: DetectionSettings,
+    readings: Sender<PitchReading>,
+    input: &cpal::Device,
+    sample_rate: u32,
+) -> Result<(), String> {
+    let mut tracker = PitchTracker::new(settings, sample_rate);
+    let (done_tx, done_rx) = mpsc::channel();
+    let error_tx = done_tx.clone();
+
+    let input_callback = move |data: &[f32]| {
+        for reading in tracker.push(data) {
+            if readings.send(reading).is_err() {
+                done_tx.send(Ok(())).ok();
             }
         }
-
-        regularize_fps(tick_start, UPDATE_FPS);
-    }
-
-    stream.pause().unwrap();
-    None
-}
-
-fn get_note(f: f64, cent_threshold: CentDeviation) -> Option<(SimpleNote, CentDeviation)> {
-    let (note, deviation) = match f > 0.0 {
-        true => closest_note(f),
-        false => return None,
-    };
-    match deviation.abs() < cent_threshold {
-        true => Some((note, deviation)),
-        false => None,
-    }
-}
-
-fn closest_note(f: f64) -> (SimpleNote, CentDeviation) {
-    let cents_from_a4 = cents_between(440.0, f);
-    let cents_from_c_min_1 = cents_from_a4 + 69 * 100;
-
-    let positive_deviation: CentDeviation = cents_from_c_min_1.rem_euclid(100).try_into().unwrap();
-
-    let floor_note = {
-        let semitones_from_c_min_1 = cents_from_c_min_1 / 100;
-        let semitones_from_c_min_1: i8 = semitones_from_c_min_1.try_into().unwrap();
-        SimpleNote::new(semitones_from_c_min_1)
     };
 
-    match positive_deviation < 50 {
-        true => (floor_note, positive_deviation),
-        false => (floor_note.shift(1), 100 - positive_deviation),
-    }
-}
+    let stream = open_input_stream(input, sample_rate, input_callback, move |e| {
+        error_tx
+            .send(Err(format!("the input device stopped: {e}")))
+            .ok();
+    })?;
+    stream.play().map_err(|e| e.to_string())?;
+    let done = done_rx.recv().unwrap_or(Ok(()));
+    stream.pause().ok();
 
-fn regularize_fps(tick_start: Instant, target_fps: u8) {
-    let target_tick_duration = Duration::from_millis((1000.0 / target_fps as f64) as u64);
-    let actual_tick_duration = Instant::now().duration_since(tick_start);
-    let to_wait = target_tick_duration.saturating_sub(actual_tick_duration);
-    std::thread::sleep(to_wait);
+    done
 }
 
-fn setup_input_device() -> Result<(Host, Device), &'static str> {
-    let host: Host = cpal::default_host();
-    let device: Device = match host.default_input_device() {
-        Some(device) => device,
-        None => return Err("no input device available"),
+/// A mono stream from the input device
+fn open_input_stream(
+    input: &cpal::Device,
+    sample_rate: u32,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+    on_error: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream, String> {
+    let config = StreamConfig {
+        channels: 1,
+        sample_rate: cpal::SampleRate(sample_rate),
+        buffer_size: cpal::BufferSize::Default,
     };
 
-    Ok((host, device))
-}
-
-fn are_octaves_away(n1: SimpleNote, n2: SimpleNote) -> bool {
-    (n1.get_i8() - n2.get_i8()) % 12 == 0
-}
-
-fn cents_between(f0: f64, f: f64) -> i32 {
-    (1200.0 * f64::log2(f / f0)) as i32
+    input
+        .build_input_stream::<f32, _, _>(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| on_samples(data),
+            on_error,
+            None,
+        )
+        .map_err(|e| e.to_string())
 }
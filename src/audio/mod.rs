@@ -1,3 +1,7 @@
+pub mod backend;
+pub mod detection;
+pub mod devices;
 pub mod listen;
 pub mod play_sample;
 pub mod synth;
+pub mod wav;
@@ -1,25 +1,23 @@
-use rodio::{source::Source, Decoder, OutputStream};
+use rodio::{source::Source, Decoder, OutputStreamHandle};
 use std::fs::File;
 use std::io::BufReader;
+use std::time::Duration;
 
-pub fn play_ding_in_thread() {
-    std::thread::spawn(|| {
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let file = BufReader::new(File::open("src/assets/audio/ding.wav").unwrap());
-        let source = Decoder::new(file).unwrap();
+pub const DING: &str = "src/assets/audio/ding.wav";
+pub const WRONG_SOUND_EFFECT: &str = "src/assets/audio/wrong.wav";
 
-        let _ = stream_handle.play_raw(source.convert_samples());
-        std::thread::sleep(std::time::Duration::from_millis(300));
-    });
-}
-
-pub fn play_wrong_sound_effect_in_thread() {
-    std::thread::spawn(|| {
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let file = BufReader::new(File::open("src/assets/audio/wrong.wav").unwrap());
-        let source = Decoder::new(file).unwrap();
+/// Blocks for `length`, the sample is cut short if it's any longer
+pub fn play_sample(
+    path: &str,
+    length: Duration,
+    output: &OutputStreamHandle,
+) -> Result<(), String> {
+    let file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let source = Decoder::new(file).map_err(|e| e.to_string())?;
 
-        let _ = stream_handle.play_raw(source.convert_samples());
-        std::thread::sleep(std::time::Duration::from_millis(3000));
-    });
+    output
+        .play_raw(source.convert_samples())
+        .map_err(|e| e.to_string())?;
+    std::thread::sleep(length);
+    Ok(())
 }
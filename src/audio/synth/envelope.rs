@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use rodio::source::Source;
+
+/// How fast the exponential curve settles, higher is snappier
+const EXPONENTIAL_STEEPNESS: f32 = 5.0;
+
+/// Shape of every segment of the envelope
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// fast at first then slowing down, like an analog envelope
+    #[default]
+    Exponential,
+}
+
+impl Curve {
+    /// Progress through a segment, from 0 to 1, for `t` from 0 to 1
+    fn shape(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Curve::Linear => t,
+            Curve::Exponential => {
+                (1.0 - (-EXPONENTIAL_STEEPNESS * t).exp()) / (1.0 - (-EXPONENTIAL_STEEPNESS).exp())
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Adsr {
+    pub attack: Duration,
+    pub decay: Duration,
+    /// level held after the decay, 1 being the peak
+    pub sustain: f32,
+    pub release: Duration,
+    pub curve: Curve,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Adsr {
+            attack: Duration::from_millis(15),
+            decay: Duration::from_millis(150),
+            sustain: 0.8,
+            release: Duration::from_millis(200),
+            curve: Curve::Exponential,
+        }
+    }
+}
+
+/// A `rodio::Source` adapter applying an `Adsr`, counted in frames so every sample gets its
+/// exact gain. The note is released after `note_length` and the source ends with the release
+pub struct Enveloped<S> {
+    source: S,
+    curve: Curve,
+    sustain: f32,
+    channels: u16,
+    sample_rate: u32,
+    attack: u64,
+    decay: u64,
+    release: u64,
+    held: u64,
+    // samples, not frames, so that every channel of a frame gets the same gain
+    n_samples: u64,
+}
+
+impl<S: Source<Item = f32>> Enveloped<S> {
+    pub fn new(source: S, envelope: Adsr, note_length: Duration) -> Self {
+        let sample_rate = source.sample_rate();
+        let to_frames = |duration: Duration| (duration.as_secs_f64() * sample_rate as f64) as u64;
+
+        Enveloped {
+            channels: source.channels(),
+            sample_rate,
+            curve: envelope.curve,
+            sustain: envelope.sustain,
+            attack: to_frames(envelope.attack),
+            decay: to_frames(envelope.decay),
+            release: to_frames(envelope.release),
+            held: to_frames(note_length),
+            n_samples: 0,
+            source,
+        }
+    }
+
+    /// Gain at the given frame, 0 once the release is over
+    pub fn gain(&self, frame: u64) -> f32 {
+        if frame < self.held {
+            return self.held_gain(frame);
+        }
+
+        // released from wherever the note was, even mid-attack
+        let released = self
+            .curve
+            .shape(segment_progress(frame - self.held, self.release));
+        self.held_gain(self.held) * (1.0 - released)
+    }
+
+    fn held_gain(&self, frame: u64) -> f32 {
+        if frame < self.attack {
+            return self.curve.shape(segment_progress(frame, self.attack));
+        }
+
+        let decayed = self
+            .curve
+            .shape(segment_progress(frame - self.attack, self.decay));
+        1.0 + (self.sustain - 1.0) * decayed
+    }
+
+    fn total_frames(&self) -> u64 {
+        self.held + self.release
+    }
+}
+
+fn segment_progress(frame: u64, length: u64) -> f32 {
+    match length {
+        0 => 1.0,
+        _ => frame as f32 / length as f32,
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Enveloped<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.n_samples / self.channels as u64;
+        if frame >= self.total_frames() {
+            return None;
+        }
+
+        let sample = self.source.next()?;
+        self.n_samples += 1;
+        Some(self.gain(frame) * sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Enveloped<S> {
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f64(
+            self.total_frames() as f64 / self.sample_rate as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::source::SineWave;
+
+    const SAMPLE_RATE: u64 = 48_000;
+
+    fn envelope(curve: Curve) -> Adsr {
+        Adsr {
+            attack: Duration::from_millis(10),
+            decay: Duration::from_millis(20),
+            sustain: 0.5,
+            release: Duration::from_millis(40),
+            curve,
+        }
+    }
+
+    // rodio's sine wave is at 48 kHz
+    fn enveloped(curve: Curve, note_length_ms: u64) -> Enveloped<SineWave> {
+        Enveloped::new(
+            SineWave::new(440),
+            envelope(curve),
+            Duration::from_millis(note_length_ms),
+        )
+    }
+
+    fn ms(ms: u64) -> u64 {
+        ms * SAMPLE_RATE / 1000
+    }
+
+    #[test]
+    fn linear_segments() {
+        let note = enveloped(Curve::Linear, 100);
+
+        assert_eq!(note.gain(0), 0.0);
+        assert_eq!(note.gain(ms(5)), 0.5);
+        assert_eq!(note.gain(ms(10)), 1.0);
+        assert_eq!(note.gain(ms(20)), 0.75);
+        assert_eq!(note.gain(ms(30)), 0.5);
+        assert_eq!(note.gain(ms(99)), 0.5);
+        assert_eq!(note.gain(ms(120)), 0.25);
+        assert_eq!(note.gain(ms(140)), 0.0);
+    }
+
+    #[test]
+    fn exponential_segments_move_faster_at_first() {
+        let note = enveloped(Curve::Exponential, 100);
+
+        assert!(note.gain(ms(5)) > 0.9);
+        assert_eq!(note.gain(ms(10)), 1.0);
+        assert!(note.gain(ms(20)) < 0.6);
+        assert!(note.gain(ms(120)) < 0.05);
+        assert_eq!(note.gain(ms(140)), 0.0);
+    }
+
+    #[test]
+    fn short_notes_are_released_without_a_jump() {
+        let note = enveloped(Curve::Linear, 5);
+
+        assert!((note.gain(ms(5) - 1) - note.gain(ms(5))).abs() < 0.01);
+        // half way through the attack
+        assert_eq!(note.gain(ms(5)), 0.5);
+    }
+
+    #[test]
+    fn source_ends_with_the_release() {
+        let note = enveloped(Curve::Exponential, 100);
+
+        assert_eq!(note.total_duration(), Some(Duration::from_millis(140)));
+        assert_eq!(note.count() as u64, ms(140));
+    }
+}
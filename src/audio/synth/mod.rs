@@ -1,4 +1,6 @@
+pub mod envelope;
+#[allow(clippy::module_inception)]
 pub mod synth;
 pub mod wavetables;
 
-pub use synth::play_notes_in_thread;
+pub use synth::{play_chords, play_notes, render_chords, render_exercise, render_notes, Timbre};
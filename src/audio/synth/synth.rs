@@ -1,139 +1,297 @@
-use std::sync::mpsc::Sender;
-use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::fmt;
+use std::time::Duration;
 
-use rodio::source::Source;
-use rodio::{OutputStream, OutputStreamHandle, Sink};
+use rodio::source::{Amplify, Source, Zero};
+use rodio::{OutputStreamHandle, Sink};
 
-use crate::audio::synth;
-use crate::music::Note;
-
-static SQUARE8_WAVETABLE: Wavetable = Wavetable::square8();
+use crate::audio::synth::envelope::{Adsr, Enveloped};
+use crate::audio::synth::wavetables;
+use crate::music::{Note, Presentation};
 
 const GAIN: f32 = 0.7;
+/// silence between the release of the first note and the second one
+const NOTE_GAP: Duration = Duration::from_secs(1);
+
+pub fn play_notes(
+    n1: Note,
+    n2: Note,
+    presentation: Presentation,
+    note_length: Duration,
+    timbre: Timbre,
+    output: &OutputStreamHandle,
+    sample_rate: u32,
+) -> Result<(), String> {
+    let synth = WavetableSynth::new(timbre.wavetable(), sample_rate);
+    let sink = Sink::try_new(output).map_err(|e| e.to_string())?;
+
+    match presentation {
+        Presentation::Melodic => {
+            synth.play(n1.frequency(), note_length, &sink);
+            sink.append(Zero::<f32>::new(1, sample_rate).take_duration(NOTE_GAP));
+            synth.play(n2.frequency(), note_length, &sink);
+        }
+        Presentation::Harmonic => {
+            sink.append(synth.chord(&[n1.frequency(), n2.frequency()], note_length));
+        }
+    }
+    sink.sleep_until_end();
+
+    Ok(())
+}
 
-pub fn play_notes(n1: Note, n2: Note, note_length: Duration, sample_rate: u16) {
-    let synth = WavetableSynth::new(SQUARE8_WAVETABLE, sample_rate);
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+/// One chord after the other, a chord being any number of notes, a single note included
+pub fn play_chords(
+    chords: &[Vec<Note>],
+    chord_length: Duration,
+    timbre: Timbre,
+    output: &OutputStreamHandle,
+    sample_rate: u32,
+) -> Result<(), String> {
+    let synth = WavetableSynth::new(timbre.wavetable(), sample_rate);
+    let sink = Sink::try_new(output).map_err(|e| e.to_string())?;
+
+    for chord in chords {
+        let frequencies: Vec<f64> = chord.iter().map(Note::frequency).collect();
+        sink.append(synth.chord(&frequencies, chord_length));
+    }
+    sink.sleep_until_end();
 
-    synth.play(n1.frequency(), note_length, &stream_handle);
-    sleep(Duration::from_secs(1));
-    synth.play(n2.frequency(), note_length, &stream_handle);
+    Ok(())
 }
 
-pub fn play_notes_in_thread(
+/// The chords as `play_chords` would play them, in a single buffer
+pub fn render_chords(
+    chords: &[Vec<Note>],
+    chord_length: Duration,
+    timbre: Timbre,
+    sample_rate: u32,
+) -> Vec<f32> {
+    let synth = WavetableSynth::new(timbre.wavetable(), sample_rate);
+
+    chords
+        .iter()
+        .flat_map(|chord| {
+            let frequencies: Vec<f64> = chord.iter().map(Note::frequency).collect();
+            synth.chord(&frequencies, chord_length)
+        })
+        .collect()
+}
+
+/// The notes as `play_notes` would play them melodically, one buffer per note
+pub fn render_notes(
     n1: Note,
     n2: Note,
     note_length: Duration,
-    sample_rate: u16,
-    signal: Sender<()>,
-) {
-    std::thread::spawn(move || {
-        play_notes(n1, n2, note_length, sample_rate);
-        signal.send(()).ok();
-    });
+    timbre: Timbre,
+    sample_rate: u32,
+) -> [Vec<f32>; 2] {
+    let synth = WavetableSynth::new(timbre.wavetable(), sample_rate);
+
+    [
+        synth.note(n1.frequency(), note_length).collect(),
+        synth.note(n2.frequency(), note_length).collect(),
+    ]
+}
+
+/// Both notes in a single buffer, `spacing` being the silence between the release of the first
+/// note and the second one when they aren't played together
+pub fn render_exercise(
+    n1: Note,
+    n2: Note,
+    presentation: Presentation,
+    note_length: Duration,
+    spacing: Duration,
+    timbre: Timbre,
+    sample_rate: u32,
+) -> Vec<f32> {
+    let synth = WavetableSynth::new(timbre.wavetable(), sample_rate);
+
+    match presentation {
+        Presentation::Melodic => {
+            let silence = (spacing.as_secs_f64() * sample_rate as f64) as usize;
+            synth
+                .note(n1.frequency(), note_length)
+                .chain(std::iter::repeat_n(0.0, silence))
+                .chain(synth.note(n2.frequency(), note_length))
+                .collect()
+        }
+        Presentation::Harmonic => synth
+            .chord(&[n1.frequency(), n2.frequency()], note_length)
+            .collect(),
+    }
 }
 
 pub struct WavetableSynth {
     wavetable: Wavetable,
-    sample_rate: u16,
-    vca: Vca,
+    sample_rate: u32,
+    envelope: Adsr,
 }
 
 impl WavetableSynth {
-    pub fn play(&self, frequency: f64, note_length: Duration, handle: &OutputStreamHandle) {
-        let sink = Sink::try_new(handle).expect("Failed to create a new sink for audio playback");
-        sink.set_volume(0.0);
+    pub fn new(wavetable: Wavetable, sample_rate: u32) -> Self {
+        WavetableSynth { wavetable, sample_rate, envelope: Adsr::default() }
+    }
 
-        let mut oscillator = Oscillator::new(self.sample_rate, self.wavetable);
-        oscillator.set_frequency(frequency);
-        sink.append(oscillator);
+    pub fn with_envelope(self, envelope: Adsr) -> Self {
+        WavetableSynth { envelope, ..self }
+    }
 
-        let note_start = Instant::now();
-        let update_period = Duration::from_millis(5);
+    /// Queues the note on the sink without waiting for it
+    pub fn play(&self, frequency: f64, note_length: Duration, sink: &Sink) {
+        sink.append(self.note(frequency, note_length));
+    }
 
-        while Instant::now().duration_since(note_start) <= note_length + self.vca.release {
-            let start_tick = Instant::now();
+    /// A single note, envelope included, that ends with its release
+    pub fn note(&self, frequency: f64, note_length: Duration) -> Amplify<Enveloped<Voices>> {
+        self.chord(&[frequency], note_length)
+    }
 
-            sink.set_volume(
-                GAIN * self
-                    .vca
-                    .get(Instant::now().duration_since(note_start), note_length),
-            );
+    /// Several notes sounding together under a single envelope
+    pub fn chord(&self, frequencies: &[f64], note_length: Duration) -> Amplify<Enveloped<Voices>> {
+        let oscillators = frequencies
+            .iter()
+            .map(|&frequency| {
+                let mut oscillator = Oscillator::new(self.sample_rate, self.wavetable);
+                oscillator.set_frequency(frequency);
+                oscillator
+            })
+            .collect();
+
+        Enveloped::new(Voices::new(oscillators), self.envelope, note_length).amplify(GAIN)
+    }
+}
 
-            sleep(update_period.saturating_sub(Instant::now().duration_since(start_tick)));
-        }
+/// The sound of the synth, to be close to the student's own instrument
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Timbre {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+    Saw,
+    Organ,
+    Clarinet,
+    ElectricPiano,
+}
 
-        sink.stop();
+impl Timbre {
+    pub fn all() -> [Timbre; 7] {
+        [
+            Timbre::Square,
+            Timbre::Sine,
+            Timbre::Triangle,
+            Timbre::Saw,
+            Timbre::Organ,
+            Timbre::Clarinet,
+            Timbre::ElectricPiano,
+        ]
     }
 
-    pub fn new(wavetable: Wavetable, sample_rate: u16) -> Self {
-        WavetableSynth {
-            wavetable,
-            sample_rate,
-            vca: Vca {
-                attack: Duration::from_millis(500),
-                sustain: 1.0,
-                release: Duration::from_millis(500),
-            },
+    pub fn wavetable(&self) -> Wavetable {
+        match self {
+            Timbre::Square => Wavetable::square8(),
+            Timbre::Sine => Wavetable::sine(),
+            Timbre::Triangle => Wavetable::triangle(),
+            Timbre::Saw => Wavetable::saw(),
+            Timbre::Organ => Wavetable::organ(),
+            Timbre::Clarinet => Wavetable::clarinet(),
+            Timbre::ElectricPiano => Wavetable::electric_piano(),
         }
     }
-}
 
-pub struct Vca {
-    attack: Duration,
-    sustain: f32,
-    release: Duration,
+    pub fn parse(string: &str) -> Result<Self, String> {
+        Timbre::all()
+            .into_iter()
+            .find(|timbre| timbre.to_string() == string)
+            .ok_or(format!(
+                "invalid timbre: {string} (expected one of {})",
+                Timbre::all().map(|t| t.to_string()).join(", ")
+            ))
+    }
 }
 
-impl Vca {
-    pub fn new(attack: Duration, sustain: f32, release: Duration) -> Self {
-        Vca { attack, sustain, release }
+impl fmt::Display for Timbre {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Timbre::Square => "square",
+            Timbre::Sine => "sine",
+            Timbre::Triangle => "triangle",
+            Timbre::Saw => "saw",
+            Timbre::Organ => "organ",
+            Timbre::Clarinet => "clarinet",
+            Timbre::ElectricPiano => "electric-piano",
+        };
+        write!(f, "{}", repr)
     }
+}
 
-    pub fn get(&self, from_start: Duration, length: Duration) -> f32 {
-        if from_start < self.attack {
-            return interpolate(
-                duration_to_millis(from_start),
-                0.0,
-                duration_to_millis(self.attack),
-                0.0,
-                self.sustain,
-            );
-        }
+/// Oscillators mixed down to a single channel
+///
+/// Every voice is scaled down by the number of voices so that the mix peaks no higher than a
+/// single voice would, whatever the phases. A chord is then about as loud as a single note
+pub struct Voices {
+    oscillators: Vec<Oscillator>,
+    sample_rate: u32,
+    voice_gain: f32,
+}
 
-        if from_start < length {
-            return self.sustain;
+impl Voices {
+    pub fn new(oscillators: Vec<Oscillator>) -> Self {
+        Voices {
+            sample_rate: oscillators.first().map_or(44_100, |o| o.sample_rate),
+            voice_gain: 1.0 / oscillators.len().max(1) as f32,
+            oscillators,
         }
+    }
+}
 
-        if from_start - length < self.release {
-            return interpolate(
-                duration_to_millis(from_start - length),
-                0.0,
-                duration_to_millis(self.release),
-                self.sustain,
-                0.0,
-            );
-        }
+impl Iterator for Voices {
+    type Item = f32;
 
-        0.0
+    fn next(&mut self) -> Option<Self::Item> {
+        let mix: f32 = self.oscillators.iter_mut().map(|o| o.get_sample()).sum();
+        Some(self.voice_gain * mix)
+    }
+}
+
+impl Source for Voices {
+    fn channels(&self) -> u16 {
+        1
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
     }
 }
 
 /// A wavetable oscillator that can play sound via the `rodio::source::Source` trait
 pub struct Oscillator {
-    sample_rate: u16,
+    sample_rate: u32,
     wavetable: Wavetable,
+    /// the table of the wavetable for the current frequency
+    plot: &'static [f32; wavetables::RESOLUTION],
     index: f32,
     index_increment: f32,
 }
 
 impl Oscillator {
-    pub fn new(sample_rate: u16, wavetable: Wavetable) -> Self {
-        Oscillator { sample_rate, wavetable, index: 0., index_increment: 0. }
+    pub fn new(sample_rate: u32, wavetable: Wavetable) -> Self {
+        Oscillator {
+            sample_rate,
+            wavetable,
+            plot: wavetable.plot_for(0.0, sample_rate),
+            index: 0.,
+            index_increment: 0.,
+        }
     }
 
     pub fn set_frequency(&mut self, frequency: f64) {
+        self.plot = self.wavetable.plot_for(frequency, self.sample_rate);
         // how much to move in the wavetable per tick
         // linear in self.wavetable.resolution()
         // linear in frequency (higher f => bigger increment to get more periods per units of time)
@@ -145,11 +303,20 @@ impl Oscillator {
     pub fn get_sample(&mut self) -> f32 {
         // the wavetable is discrete so non-integer values must be estimated
         // here by linear interpolation
-        let sample = self.wavetable.at(self.index);
+        let sample = self.at(self.index);
         self.index += self.index_increment;
         self.index %= self.wavetable.resolution() as f32;
         sample
     }
+
+    fn at(&self, index: f32) -> f32 {
+        let left_index = index as usize;
+        let right_index = (left_index + 1) % self.wavetable.resolution();
+        let right_weight = index - (left_index as f32);
+        let left_weight = 1.0 - right_weight;
+
+        left_weight * self.plot[left_index] + right_weight * self.plot[right_index]
+    }
 }
 
 // required for the `Source` trait
@@ -161,27 +328,55 @@ impl Iterator for Oscillator {
     }
 }
 
+/// A cycle of the sound, band-limited: each note is played from the table with as many
+/// harmonics as fit under the Nyquist frequency
 #[derive(Copy, Clone)]
 pub struct Wavetable {
-    pub plot: &'static [f32; 1024],
+    pub plots: &'static wavetables::BandLimited,
 }
 
 impl Wavetable {
-    pub const fn square8() -> Self {
-        Wavetable { plot: &synth::wavetables::SQUARE_8 }
+    pub fn square8() -> Self {
+        Wavetable { plots: wavetables::square8() }
     }
 
-    pub fn at(&self, index: f32) -> f32 {
-        let left_index = index as usize;
-        let right_index = (left_index + 1) % self.resolution();
-        let right_weight = index - (left_index as f32);
-        let left_weight = 1.0 - right_weight;
+    pub fn sine() -> Self {
+        Wavetable { plots: wavetables::sine() }
+    }
 
-        left_weight * self.plot[left_index] + right_weight * self.plot[right_index]
+    pub fn triangle() -> Self {
+        Wavetable { plots: wavetables::triangle() }
+    }
+
+    pub fn saw() -> Self {
+        Wavetable { plots: wavetables::saw() }
+    }
+
+    pub fn organ() -> Self {
+        Wavetable { plots: wavetables::organ() }
+    }
+
+    pub fn clarinet() -> Self {
+        Wavetable { plots: wavetables::clarinet() }
+    }
+
+    pub fn electric_piano() -> Self {
+        Wavetable { plots: wavetables::electric_piano() }
+    }
+
+    /// The table with the most harmonics that don't alias at that frequency
+    pub fn plot_for(
+        &self,
+        frequency: f64,
+        sample_rate: u32,
+    ) -> &'static [f32; wavetables::RESOLUTION] {
+        let n_harmonics = (sample_rate as f64 / 2.0 / frequency).floor().max(1.0);
+        let index = (n_harmonics.log2().floor() as usize).min(self.plots.len() - 1);
+        &self.plots[index]
     }
 
     pub fn resolution(&self) -> usize {
-        1024
+        wavetables::RESOLUTION
     }
 }
 
@@ -192,7 +387,7 @@ impl Source for Oscillator {
         1
     }
     fn sample_rate(&self) -> u32 {
-        self.sample_rate as u32
+        self.sample_rate
     }
     fn current_frame_len(&self) -> Option<usize> {
         // > Returns the number of samples before the current frame ends. None means “infinite” or
@@ -206,13 +401,53 @@ impl Source for Oscillator {
     }
 }
 
-fn duration_to_millis(duration: Duration) -> f32 {
-    (duration.as_secs() as f32) * 1000.0 + (duration.subsec_millis() as f32)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::detection::{detect_note, DetectionSettings, OctaveMatching};
+
+    #[test]
+    fn chords_have_headroom() {
+        let synth = WavetableSynth::new(Wavetable::square8(), 44_100);
+        let peak = |source: Amplify<Enveloped<Voices>>| source.fold(0.0_f32, |m, s| m.max(s.abs()));
 
-fn interpolate(current: f32, start: f32, end: f32, start_value: f32, end_value: f32) -> f32 {
-    let high_contribution = (current - start) / (end - start);
-    let low_contribution = (end - current) / (end - start);
+        let single = peak(synth.note(220.0, Duration::from_millis(300)));
+        let triad = peak(synth.chord(&[220.0, 277.18, 329.63], Duration::from_millis(300)));
 
-    high_contribution * end_value + low_contribution * start_value
+        assert!(single <= GAIN);
+        assert!(triad <= single);
+        assert!(triad > 0.5 * single);
+    }
+
+    #[test]
+    fn high_notes_have_no_harmonics_above_nyquist() {
+        let saw = Wavetable::saw();
+        // room for 220 harmonics at A1, more than the table has
+        assert_eq!(saw.plot_for(55.0, 22_050), saw.plots.last().unwrap());
+        // room for 7 harmonics at 1.5 kHz, so 4 of them
+        assert_eq!(saw.plot_for(1500.0, 22_050), &saw.plots[2]);
+        // above Nyquist, at least the fundamental
+        assert_eq!(saw.plot_for(20_000.0, 22_050), &saw.plots[0]);
+    }
+
+    #[test]
+    fn every_timbre_is_heard_at_its_pitch() {
+        let g4 = Note::parse_from_string("G4").unwrap();
+
+        for timbre in Timbre::all() {
+            let [note, _] = render_notes(g4, g4, Duration::from_millis(1000), timbre, 44_100);
+            let outcome = detect_note(
+                note,
+                g4.to_simple(),
+                OctaveMatching::Tolerant,
+                DetectionSettings::default(),
+                44_100,
+            );
+            assert_eq!(
+                outcome.heard.map(|h| h.note),
+                Some(g4.to_simple()),
+                "{timbre}"
+            );
+        }
+    }
 }
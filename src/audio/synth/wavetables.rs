@@ -1,4 +1,8 @@
-pub const SQUARE_8: [f32; 1024] = [
+use std::sync::OnceLock;
+
+pub const RESOLUTION: usize = 1024;
+
+pub const SQUARE_8: [f32; RESOLUTION] = [
     0.0,
     0.0263851,
     0.0527493,
@@ -1024,3 +1028,134 @@ pub const SQUARE_8: [f32; 1024] = [
     -0.0527485,
     -0.026384,
 ];
+
+/// A single cycle built from the amplitudes of the harmonics, fundamental first. Negative
+/// amplitudes flip the phase of that harmonic. The table is normalized to a peak of 1
+pub fn from_harmonics(amplitudes: &[f32]) -> [f32; RESOLUTION] {
+    let mut table = [0.0; RESOLUTION];
+    for (i, sample) in table.iter_mut().enumerate() {
+        let phase = 2.0 * std::f64::consts::PI * i as f64 / RESOLUTION as f64;
+        *sample = amplitudes
+            .iter()
+            .enumerate()
+            .map(|(n, &amplitude)| amplitude as f64 * ((n + 1) as f64 * phase).sin())
+            .sum::<f64>() as f32;
+    }
+
+    let peak = table.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+    if peak > 0.0 {
+        table.iter_mut().for_each(|s| *s /= peak);
+    }
+    table
+}
+
+/// Harmonics up to this one at most, which low notes have room for
+const MAX_HARMONICS: usize = 64;
+
+/// The same cycle with up to 1, 2, 4, ... of its harmonics, one table per octave, so that every
+/// note can be played with only the harmonics that fit under the Nyquist frequency
+pub type BandLimited = Vec<[f32; RESOLUTION]>;
+
+fn band_limited(amplitudes: &[f32]) -> BandLimited {
+    let mut tables = Vec::new();
+    let mut n_harmonics = 1;
+    loop {
+        tables.push(from_harmonics(
+            &amplitudes[..n_harmonics.min(amplitudes.len())],
+        ));
+        if n_harmonics >= amplitudes.len() {
+            return tables;
+        }
+        n_harmonics *= 2;
+    }
+}
+
+/// `SQUARE_8` for the notes with room for all of its harmonics, up to the 15th
+pub fn square8() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let amplitudes: Vec<f32> = (1..=8)
+            .map(|n| match n % 2 {
+                1 => 1.0 / n as f32,
+                _ => 0.0,
+            })
+            .collect();
+        let mut tables = band_limited(&amplitudes);
+        tables.push(SQUARE_8);
+        tables
+    })
+}
+
+pub fn sine() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| band_limited(&[1.0]))
+}
+
+/// odd harmonics only, falling off with their square
+pub fn triangle() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let amplitudes: Vec<f32> = (1..=MAX_HARMONICS)
+            .map(|n| match n % 4 {
+                1 => 1.0 / (n * n) as f32,
+                3 => -1.0 / (n * n) as f32,
+                _ => 0.0,
+            })
+            .collect();
+        band_limited(&amplitudes)
+    })
+}
+
+/// every harmonic, falling off with its rank
+pub fn saw() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let amplitudes: Vec<f32> = (1..=MAX_HARMONICS).map(|n| 1.0 / n as f32).collect();
+        band_limited(&amplitudes)
+    })
+}
+
+/// drawbars at 8', 4', 2 2/3', 2' and 1'
+pub fn organ() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| band_limited(&[1.0, 0.8, 0.5, 0.6, 0.0, 0.0, 0.0, 0.4]))
+}
+
+/// mostly odd harmonics, like any closed pipe
+pub fn clarinet() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        band_limited(&[
+            1.0, 0.04, 0.75, 0.05, 0.5, 0.06, 0.3, 0.03, 0.15, 0.02, 0.08,
+        ])
+    })
+}
+
+/// a strong fundamental with a few soft upper partials
+pub fn electric_piano() -> &'static BandLimited {
+    static TABLES: OnceLock<BandLimited> = OnceLock::new();
+    TABLES.get_or_init(|| band_limited(&[1.0, 0.3, 0.1, 0.15, 0.02, 0.05, 0.06]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harmonic_tables() {
+        let sine = &sine()[0];
+        assert_eq!(sine[0], 0.0);
+        assert!((sine[RESOLUTION / 4] - 1.0).abs() < 1e-6);
+        assert!((sine[3 * RESOLUTION / 4] + 1.0).abs() < 1e-6);
+
+        for tables in [triangle(), saw(), organ(), clarinet(), electric_piano()] {
+            for table in tables {
+                let peak = table.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+                assert!((peak - 1.0).abs() < 1e-6);
+            }
+        }
+        // one table per octave of harmonics, the first being a sine
+        assert_eq!(saw().len(), 7);
+        assert_eq!(saw()[0], *sine);
+    }
+}
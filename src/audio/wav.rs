@@ -0,0 +1,20 @@
+use std::path::Path;
+
+/// Writes a 16 bit mono WAV file, samples beyond [-1, 1] are clipped
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32], sample_rate: u32) -> Result<(), String> {
+    let path = path.as_ref();
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let error = |e: hound::Error| format!("{}: {e}", path.display());
+
+    let mut writer = hound::WavWriter::create(path, spec).map_err(error)?;
+    for &sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(sample).map_err(error)?;
+    }
+    writer.finalize().map_err(error)
+}
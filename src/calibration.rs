@@ -0,0 +1,371 @@
+//! Detection thresholds measured for an input device, kept on disk across sessions
+//!
+//! Calibrating listens to the room in silence, then to the user playing or singing a long note,
+//! and suggests thresholds in between. Calibrations are kept in a JSON lines file next to the
+//! practice history, one line per input device, for instance
+//!
+//! ```text
+//! {"device":"USB Audio CODEC","noise_floor":0.42,"level":96.3,"power_threshold":6.36,
+//!  "clarity_threshold":0.81,"buffer_size":1024}
+//! ```
+//!
+//! (on a single line)
+//!
+//! - `device`: the name of the input device
+//! - `noise_floor`, `level`: power of the room in silence and of the user's instrument, as sums of
+//!   the squared samples of a detection buffer
+//! - `power_threshold`, `clarity_threshold`: what the pitch detection uses for that device
+//! - `buffer_size`: the detection buffer size the powers were measured with, they're rescaled
+//!   when detecting with another one. 1024 if missing
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::audio::detection::{DetectionSettings, PitchReading};
+use crate::history::{data_dir, escape, parse_flat_object, warn_skipped, Value, APP_DIR};
+
+pub const CALIBRATION_FILE: &str = "calibration.jsonl";
+
+/// How long the room is listened to in silence
+pub const QUIET: Duration = Duration::from_secs(3);
+/// How long the user is listened to afterwards
+pub const PLAYING: Duration = Duration::from_secs(4);
+
+/// Buffers this many times louder than the noise floor are the user playing
+const ABOVE_THE_NOISE: f32 = 4.0;
+/// So that a dead quiet room doesn't make every sound count, for a buffer of the default size
+const MIN_NOISE_FLOOR: f32 = 0.01;
+/// About a quarter of a second of playing at 44.1kHz with the default buffer size
+const MIN_READINGS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub device: String,
+    pub noise_floor: f32,
+    pub level: f32,
+    pub power_threshold: f32,
+    pub clarity_threshold: f32,
+    pub buffer_size: usize,
+}
+
+/// What the user is asked to do while calibrating
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CalibrationStep {
+    Quiet,
+    Playing,
+    Done,
+}
+
+impl CalibrationStep {
+    /// From how long calibration has been listening for
+    pub fn at(elapsed: Duration) -> Self {
+        match elapsed {
+            elapsed if elapsed < QUIET => CalibrationStep::Quiet,
+            elapsed if elapsed < QUIET + PLAYING => CalibrationStep::Playing,
+            _ => CalibrationStep::Done,
+        }
+    }
+}
+
+impl Calibration {
+    /// What the tracker should hear while calibrating: every buffer's power, and the clarity of
+    /// anything that's remotely pitched
+    pub fn listening(settings: DetectionSettings) -> DetectionSettings {
+        DetectionSettings { power_threshold: 0.0, clarity_threshold: 0.5, ..settings }
+    }
+
+    /// Thresholds from the readings of a calibration run. The power threshold sits halfway
+    /// between the noise and the instrument on a log scale, the clarity threshold just under
+    /// what most of the user's buffers reached. The readings are of buffers of `buffer_size`
+    pub fn suggest(
+        device: String,
+        buffer_size: usize,
+        readings: &[PitchReading],
+    ) -> Result<Self, String> {
+        let min_noise_floor =
+            MIN_NOISE_FLOOR * buffer_size as f32 / DetectionSettings::DEFAULT.buffer_size as f32;
+
+        let (quiet, playing): (Vec<&PitchReading>, Vec<&PitchReading>) = readings
+            .iter()
+            .filter(|reading| CalibrationStep::at(reading.at) != CalibrationStep::Done)
+            .partition(|reading| CalibrationStep::at(reading.at) == CalibrationStep::Quiet);
+
+        let mut noise: Vec<f32> = quiet.iter().map(|reading| reading.power).collect();
+        let noise_floor = percentile(&mut noise, 0.95).unwrap_or(0.0);
+        let noise_floor_or_min = noise_floor.max(min_noise_floor);
+
+        // the user may start late or stop early
+        let loud: Vec<&PitchReading> = playing
+            .into_iter()
+            .filter(|reading| reading.power > ABOVE_THE_NOISE * noise_floor_or_min)
+            .collect();
+        if loud.len() < MIN_READINGS {
+            return Err(String::from(
+                "nothing was heard over the room noise, try closer to the microphone",
+            ));
+        }
+        let mut powers: Vec<f32> = loud.iter().map(|reading| reading.power).collect();
+        let level = percentile(&mut powers, 0.5).unwrap();
+
+        let mut clarities: Vec<f32> = loud
+            .iter()
+            .filter(|reading| reading.heard.is_some())
+            .map(|reading| reading.clarity)
+            .collect();
+        if clarities.len() < MIN_READINGS {
+            return Err(String::from("no clear pitch was heard, hold a steady note"));
+        }
+        let clarity = percentile(&mut clarities, 0.2).unwrap();
+
+        Ok(Calibration {
+            device,
+            noise_floor,
+            level,
+            power_threshold: (noise_floor_or_min * level).sqrt(),
+            clarity_threshold: (clarity - 0.05).clamp(0.5, 0.9),
+            buffer_size,
+        })
+    }
+
+    /// The settings with the thresholds of the calibration. Power adds up over a buffer, so the
+    /// threshold grows with the buffer size
+    pub fn apply(&self, settings: DetectionSettings) -> DetectionSettings {
+        DetectionSettings {
+            power_threshold: self.power_threshold * settings.buffer_size as f32
+                / self.buffer_size as f32,
+            clarity_threshold: self.clarity_threshold,
+            ..settings
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"device\":\"{}\",\"noise_floor\":{:.4},\"level\":{:.4},\
+             \"power_threshold\":{:.4},\"clarity_threshold\":{:.3},\"buffer_size\":{}}}",
+            escape(&self.device),
+            self.noise_floor,
+            self.level,
+            self.power_threshold,
+            self.clarity_threshold,
+            self.buffer_size,
+        )
+    }
+
+    pub fn from_json(line: &str) -> Result<Self, String> {
+        let fields = parse_flat_object(line)?;
+        let get = |key: &str| -> Result<&Value, String> {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or(format!("missing field {key}"))
+        };
+        let number = |key: &str| -> Result<f32, String> { Ok(get(key)?.as_number()? as f32) };
+
+        Ok(Calibration {
+            device: get("device")?.as_str()?.to_string(),
+            noise_floor: number("noise_floor")?,
+            level: number("level")?,
+            power_threshold: number("power_threshold")?,
+            clarity_threshold: number("clarity_threshold")?,
+            buffer_size: match fields.iter().find(|(k, _)| k == "buffer_size") {
+                Some((_, value)) => match value.as_number()? as usize {
+                    0 => return Err(String::from("buffer_size must be positive")),
+                    buffer_size => buffer_size,
+                },
+                None => DetectionSettings::DEFAULT.buffer_size,
+            },
+        })
+    }
+}
+
+/// The calibration file
+pub struct Calibrations {
+    path: PathBuf,
+}
+
+impl Calibrations {
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Calibrations { path: path.into() }
+    }
+
+    /// The calibration file in the user's data directory
+    pub fn open_default() -> Result<Self, String> {
+        match data_dir() {
+            Some(dir) => Ok(Calibrations::at(dir.join(APP_DIR).join(CALIBRATION_FILE))),
+            None => Err(String::from(
+                "could not find a data directory for the calibrations",
+            )),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every device calibrated so far. A file that doesn't exist yet has none, lines that can't
+    /// be read are skipped with a warning
+    pub fn load(&self) -> Result<Vec<Calibration>, String> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("{}: {e}", self.path.display())),
+        };
+
+        let mut calibrations = Vec::new();
+        let mut skipped = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Calibration::from_json(line) {
+                Ok(calibration) => calibrations.push(calibration),
+                Err(e) => skipped.push((i + 1, e)),
+            }
+        }
+        warn_skipped(&self.path, &skipped);
+
+        Ok(calibrations)
+    }
+
+    pub fn find(&self, device: &str) -> Result<Option<Calibration>, String> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .find(|calibration| calibration.device == device))
+    }
+
+    /// Replaces the calibration of the same device, if any
+    pub fn save(&self, calibration: &Calibration) -> Result<(), String> {
+        let mut calibrations = self.load()?;
+        calibrations.retain(|c| c.device != calibration.device);
+        calibrations.push(calibration.clone());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let contents: String = calibrations
+            .iter()
+            .map(|calibration| calibration.to_json() + "\n")
+            .collect();
+        fs::write(&self.path, contents).map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+}
+
+/// The value below which that share of the values fall, `None` if there are none
+fn percentile(values: &mut [f32], share: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let index = ((values.len() - 1) as f32 * share).round() as usize;
+    Some(values[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::detection::PitchTracker;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    /// Some hiss, then a note over the same hiss, in buffers of that size
+    fn run(note_amplitude: f64, buffer_size: usize) -> Vec<PitchReading> {
+        let mut seed = 1_u32;
+        let mut hiss = move || {
+            // xorshift, good enough for noise
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed as f64 / u32::MAX as f64 - 0.5) * 0.01
+        };
+        let total = (QUIET + PLAYING).as_secs_f64() * SAMPLE_RATE as f64;
+        let samples: Vec<f32> = (0..total as usize)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let note = match t < QUIET.as_secs_f64() {
+                    true => 0.0,
+                    false => note_amplitude * (2.0 * std::f64::consts::PI * 330.0 * t).sin(),
+                };
+                (note + hiss()) as f32
+            })
+            .collect();
+
+        let settings =
+            Calibration::listening(DetectionSettings { buffer_size, ..DetectionSettings::DEFAULT });
+        PitchTracker::new(settings, SAMPLE_RATE).push(&samples)
+    }
+
+    #[test]
+    fn thresholds_sit_between_the_room_and_the_instrument() {
+        let calibration = Calibration::suggest(String::from("mic"), 1024, &run(0.3, 1024)).unwrap();
+
+        assert!(calibration.noise_floor < calibration.power_threshold);
+        assert!(calibration.power_threshold < calibration.level);
+        assert!((0.5..=0.9).contains(&calibration.clarity_threshold));
+
+        let quiet = Calibration::suggest(String::from("mic"), 1024, &run(0.0, 1024));
+        assert!(quiet.is_err());
+    }
+
+    #[test]
+    fn thresholds_follow_the_buffer_size() {
+        let short = Calibration::suggest(String::from("mic"), 1024, &run(0.3, 1024)).unwrap();
+        let long = Calibration::suggest(String::from("mic"), 2048, &run(0.3, 2048)).unwrap();
+        assert!(long.power_threshold > 1.5 * short.power_threshold);
+
+        // whichever buffer size they were measured with, detecting with another one gets about
+        // the same threshold
+        for buffer_size in [512, 4096] {
+            let settings = DetectionSettings { buffer_size, ..DetectionSettings::DEFAULT };
+            let from_short = short.apply(settings).power_threshold;
+            let from_long = long.apply(settings).power_threshold;
+            assert!((from_short / from_long - 1.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn one_calibration_per_device() {
+        let path = std::env::temp_dir().join(format!("calibration_test_{}", std::process::id()));
+        let calibrations = Calibrations::at(&path);
+        let calibration = |device: &str, power_threshold| Calibration {
+            device: String::from(device),
+            noise_floor: 0.5,
+            level: 50.0,
+            power_threshold,
+            clarity_threshold: 0.75,
+            buffer_size: 1024,
+        };
+
+        calibrations
+            .save(&calibration("Built-in \"Mic\"", 5.0))
+            .unwrap();
+        calibrations.save(&calibration("USB", 8.0)).unwrap();
+        calibrations
+            .save(&calibration("Built-in \"Mic\"", 3.0))
+            .unwrap();
+
+        assert_eq!(calibrations.load().unwrap().len(), 2);
+        assert_eq!(
+            calibrations.find("Built-in \"Mic\"").unwrap(),
+            Some(calibration("Built-in \"Mic\"", 3.0))
+        );
+        assert_eq!(calibrations.find("nope").unwrap(), None);
+
+        let mut contents = fs::read_to_string(&path).unwrap();
+        // from before the buffer size was kept
+        contents.push_str(
+            "{\"device\":\"old\",\"noise_floor\":0.5,\"level\":50,\"power_threshold\":5,\
+             \"clarity_threshold\":0.75}\n",
+        );
+        contents.insert_str(0, "not json\n");
+        fs::write(&path, contents).unwrap();
+        assert_eq!(calibrations.load().unwrap().len(), 3);
+        assert_eq!(
+            calibrations.find("old").unwrap().map(|c| c.buffer_size),
+            Some(1024)
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
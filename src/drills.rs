@@ -0,0 +1,204 @@
+//! Practice audio for students who don't have the app
+//!
+//! `interval_trainer render --count 50 --out drills/` writes `drills/001.wav` to
+//! `drills/050.wav`, one exercise per file, along with an answer key in `drills/answers.csv`.
+//! A scale degree exercise is its cadence followed by the note, a chord exercise the chord held
+//! for twice as long, a melody exercise its notes without any spacing
+
+use std::fs;
+
+use crate::audio::{synth, wav};
+use crate::interval_trainer::{scheduler::Scheduler, Exercise};
+use crate::music::{Note, NoteRange, ScaleDegree};
+use crate::settings::{RenderOptions, Settings};
+
+pub const ANSWER_KEY: &str = "answers.csv";
+
+/// Draws the exercises as the app would for a new student, and writes them out
+pub fn render_drills(
+    range: &NoteRange,
+    settings: &Settings,
+    options: &RenderOptions,
+) -> Result<Vec<Exercise>, String> {
+    let out = &options.out;
+    fs::create_dir_all(out).map_err(|e| format!("{}: {e}", out.display()))?;
+
+    let scheduler = Scheduler::new();
+    // the answer key is written for the instrument, the audio sounds at concert pitch
+    let sounding = |note: Note| match settings.instrument {
+        Some(instrument) => instrument.sounding(note),
+        None => note,
+    };
+    let exercises: Vec<Exercise> = (0..options.count)
+        .map(|_| Exercise::draw(range, settings, &scheduler))
+        .collect();
+
+    let width = options.count.to_string().len().max(3);
+    let mut answer_key =
+        String::from("file,reference,target,interval,name,direction,presentation\n");
+    for (i, exercise) in exercises.iter().enumerate() {
+        let file = format!("{:0width$}.wav", i + 1);
+        let samples = match (exercise.cadence, exercise.chord, &exercise.melody) {
+            (Some(key), _, _) => {
+                let mut chords: Vec<Vec<Note>> = key
+                    .cadence(exercise.reference)
+                    .into_iter()
+                    .map(|chord| chord.into_iter().map(sounding).collect())
+                    .collect();
+                chords.push(vec![sounding(exercise.mystery_note)]);
+                synth::render_chords(
+                    &chords,
+                    options.note_length,
+                    settings.timbre,
+                    options.sample_rate,
+                )
+            }
+            (None, Some(chord), _) => synth::render_chords(
+                &[chord.notes().into_iter().map(sounding).collect()],
+                2 * options.note_length,
+                settings.timbre,
+                options.sample_rate,
+            ),
+            (None, None, Some(melody)) => synth::render_chords(
+                &melody
+                    .notes
+                    .iter()
+                    .map(|&note| vec![sounding(note)])
+                    .collect::<Vec<_>>(),
+                options.note_length,
+                settings.timbre,
+                options.sample_rate,
+            ),
+            (None, None, None) => synth::render_exercise(
+                sounding(exercise.reference),
+                sounding(exercise.mystery_note),
+                exercise.presentation,
+                options.note_length,
+                options.spacing,
+                settings.timbre,
+                options.sample_rate,
+            ),
+        };
+        let name = match (exercise.cadence, exercise.chord, &exercise.melody) {
+            (Some(key), _, _) => {
+                format!("{} in {key}", ScaleDegree::of(exercise.mystery_note, &key))
+            }
+            // the inversion comes after a comma
+            (None, Some(chord), _) => format!("\"{chord}\""),
+            (None, None, Some(melody)) => {
+                let notes: Vec<String> = melody.notes.iter().map(Note::to_string).collect();
+                format!("{} in {}", notes.join(" "), melody.key)
+            }
+            (None, None, None) => exercise.interval.to_string(),
+        };
+        wav::write_wav(out.join(&file), &samples, options.sample_rate)?;
+
+        answer_key += &format!(
+            "{file},{},{},{},{},{},{}\n",
+            exercise.reference,
+            exercise.mystery_note,
+            exercise.interval.short_repr(),
+            name,
+            exercise.direction,
+            exercise.presentation,
+        );
+    }
+
+    let answer_key_path = out.join(ANSWER_KEY);
+    fs::write(&answer_key_path, answer_key)
+        .map_err(|e| format!("{}: {e}", answer_key_path.display()))?;
+
+    Ok(exercises)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::detection::{
+        detect_note, detect_note_in_wav, DetectionSettings, OctaveMatching,
+    };
+
+    #[test]
+    fn drills_match_their_answer_key() {
+        let out = std::env::temp_dir().join(format!("drills_test_{}", std::process::id()));
+        // more than a u16 can hold
+        let options =
+            RenderOptions { count: 3, out: out.clone(), sample_rate: 96_000, ..Default::default() };
+
+        let exercises =
+            render_drills(&NoteRange::treble_staff(), &Settings::default(), &options).unwrap();
+
+        // about as long a buffer as the default one at 44.1 kHz
+        let detection = DetectionSettings { buffer_size: 2048, ..Default::default() };
+
+        let answer_key = fs::read_to_string(out.join(ANSWER_KEY)).unwrap();
+        let lines: Vec<&str> = answer_key.lines().skip(1).collect();
+        assert_eq!(lines.len(), 3);
+
+        for (i, (exercise, line)) in exercises.iter().zip(lines).enumerate() {
+            let file = format!("00{}.wav", i + 1);
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!(fields[0], file);
+            assert_eq!(fields[1], exercise.reference.to_string());
+            assert_eq!(fields[2], exercise.mystery_note.to_string());
+
+            // the reference comes first
+            let outcome = detect_note_in_wav(
+                out.join(&file),
+                exercise.reference.to_simple(),
+                OctaveMatching::Tolerant,
+                detection,
+            )
+            .unwrap();
+            assert!(outcome.success);
+
+            // then the mystery note, as long as the reference after the spacing
+            let mut reader = hound::WavReader::open(out.join(&file)).unwrap();
+            assert_eq!(reader.spec().sample_rate, 96_000);
+            let samples: Vec<f32> = reader
+                .samples::<i16>()
+                .map(|s| s.unwrap() as f32 / i16::MAX as f32)
+                .collect();
+            let spacing = (options.spacing.as_secs_f64() * 96_000.0) as usize;
+            let target = Note::parse_from_string(fields[2]).unwrap();
+            let outcome = detect_note(
+                samples[(samples.len() + spacing) / 2..].iter().copied(),
+                target.to_simple(),
+                OctaveMatching::Strict,
+                detection,
+                96_000,
+            );
+            assert!(outcome.success, "{file}: {}", fields[2]);
+        }
+
+        fs::remove_dir_all(out).unwrap();
+    }
+
+    #[test]
+    fn the_answer_key_never_needs_triple_accidentals() {
+        let out = std::env::temp_dir().join(format!("drills_all_test_{}", std::process::id()));
+        let options = RenderOptions {
+            count: 40,
+            out: out.clone(),
+            note_length: std::time::Duration::from_millis(50),
+            sample_rate: 8_000,
+            ..Default::default()
+        };
+        let settings = Settings {
+            intervals: crate::music::IntervalPool::all(),
+            key: Some(crate::music::Key::parse("C#").unwrap()),
+            ..Default::default()
+        };
+
+        render_drills(&NoteRange::piano(), &settings, &options).unwrap();
+
+        let answer_key = fs::read_to_string(out.join(ANSWER_KEY)).unwrap();
+        for line in answer_key.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            for note in &fields[1..3] {
+                assert!(Note::parse_from_string(note).is_ok(), "{line}");
+            }
+        }
+        fs::remove_dir_all(out).unwrap();
+    }
+}
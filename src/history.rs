@@ -0,0 +1,489 @@
+//! Practice history, kept on disk across sessions
+//!
+//! The history is a JSON lines file: one exercise per line, appended as soon as it's over.
+//! Every line is a flat object, for instance
+//!
+//! ```text
+//! {"version":1,"timestamp_ms":1697640000123,"drill":"intervals","reference":"C4","target":"E4",
+//!  "interval":"M3","direction":"up","presentation":"melodic","reference_success":true,
+//!  "success":true,"octaves":"tolerant","detected_frequency":329.8,"cent_deviation":-3,
+//!  "response_time_ms":640,"quality":null,"picked":null}
+//! ```
+//!
+//! (on a single line)
+//!
+//! - `version`: format version, currently 1
+//! - `timestamp_ms`: end of the exercise, in milliseconds since the unix epoch
+//! - `drill`: "intervals", or "scale-degrees" where the reference is the tonic of the key and the
+//!   target the note whose degree was asked, or "chords" where the reference is the bass of the
+//!   chord and the target the chord tone to sing, its root if the quality was picked, or
+//!   "melodies" with a line for every step of the melody, from the note before it. Older lines
+//!   without it were intervals
+//! - `quality`: for chords, the quality of the chord that was played, e.g. "minor seventh",
+//!   `null` for the other drills. Older lines may not have it
+//! - `picked`: for chords answered with the keyboard, the quality that was picked, `null` when
+//!   the chord tone was sung and for the other drills. Older lines may not have it
+//! - `reference`, `target`: the two notes that were played, e.g. "Eb4"
+//! - `interval`: short notation, e.g. "m3", "P5", "M10"
+//! - `direction`: "up" or "down"
+//! - `presentation`: "melodic" or "harmonic", whether the notes were played one after the other
+//!   or together. Older lines without it were melodic
+//! - `reference_success`: whether the reference note was matched
+//! - `success`: whether the target note was matched
+//! - `octaves`: which octaves of the target counted, "strict", "tolerant" for any octave, or
+//!   "transposed:" followed by the semitones from the written note to the one that counted, e.g.
+//!   "transposed:-12". Older lines without it were tolerant
+//! - `detected_frequency`: pitch that matched the target in Hz, or on a miss the pitch that was
+//!   heard the most, `null` if nothing was heard
+//! - `cent_deviation`: how far off that pitch was from the closest note, negative means flat,
+//!   `null` if nothing was heard
+//! - `response_time_ms`: time until the target was matched, or the full detection window on a miss
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audio::listen::{CentDeviation, OctaveMatching};
+use crate::music::{ChordQuality, Direction, Interval, Note, Presentation};
+use crate::settings::Drill;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+pub(crate) const APP_DIR: &str = "interval_trainer";
+const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub timestamp: SystemTime,
+    pub drill: Drill,
+    pub reference: Note,
+    pub target: Note,
+    pub interval: Interval,
+    pub direction: Direction,
+    pub presentation: Presentation,
+    pub reference_success: bool,
+    pub success: bool,
+    pub octaves: OctaveMatching,
+    pub detected_frequency: Option<f64>,
+    pub cent_deviation: Option<CentDeviation>,
+    pub response_time: Duration,
+    pub quality: Option<ChordQuality>,
+    pub picked: Option<ChordQuality>,
+}
+
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        History { path: path.into() }
+    }
+
+    /// The history file in the user's data directory
+    pub fn open_default() -> Result<Self, String> {
+        match data_dir() {
+            Some(dir) => Ok(History::at(dir.join(APP_DIR).join(HISTORY_FILE))),
+            None => Err(String::from(
+                "could not find a data directory for the history",
+            )),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &Record) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(|e| format!("{}: {e}", self.path.display()))?;
+        writeln!(file, "{}", record.to_json()).map_err(|e| e.to_string())
+    }
+
+    /// Every record so far, oldest first. A history that doesn't exist yet is empty, lines that
+    /// can't be read are skipped with a warning rather than losing the whole history
+    pub fn load(&self) -> Result<Vec<Record>, String> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("{}: {e}", self.path.display())),
+        };
+
+        let mut records = Vec::new();
+        let mut skipped = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Record::from_json(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => skipped.push((i + 1, e)),
+            }
+        }
+        warn_skipped(&self.path, &skipped);
+
+        Ok(records)
+    }
+}
+
+/// One warning for every line of a file that couldn't be read, with the first reason
+pub(crate) fn warn_skipped(path: &Path, skipped: &[(usize, String)]) {
+    let Some((_, first)) = skipped.first() else {
+        return;
+    };
+    let lines: Vec<String> = skipped.iter().map(|(line, _)| line.to_string()).collect();
+    eprintln!(
+        "{}: skipped {} unreadable line(s) ({}): {first}",
+        path.display(),
+        skipped.len(),
+        lines.join(", ")
+    );
+}
+
+impl Record {
+    pub fn to_json(&self) -> String {
+        let timestamp_ms = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let detected_frequency = match self.detected_frequency {
+            Some(f) => format!("{f:.2}"),
+            None => String::from("null"),
+        };
+        let cent_deviation = match self.cent_deviation {
+            Some(c) => c.to_string(),
+            None => String::from("null"),
+        };
+        let quality = |quality: Option<ChordQuality>| match quality {
+            Some(quality) => format!("\"{quality}\""),
+            None => String::from("null"),
+        };
+
+        format!(
+            "{{\"version\":{},\"timestamp_ms\":{},\"drill\":\"{}\",\"reference\":\"{}\",\
+             \"target\":\"{}\",\"interval\":\"{}\",\"direction\":\"{}\",\"presentation\":\"{}\",\
+             \"reference_success\":{},\"success\":{},\"octaves\":\"{}\",\
+             \"detected_frequency\":{},\"cent_deviation\":{},\"response_time_ms\":{},\
+             \"quality\":{},\"picked\":{}}}",
+            FORMAT_VERSION,
+            timestamp_ms,
+            self.drill,
+            self.reference,
+            self.target,
+            self.interval.short_repr(),
+            self.direction,
+            self.presentation,
+            self.reference_success,
+            self.success,
+            self.octaves,
+            detected_frequency,
+            cent_deviation,
+            self.response_time.as_millis(),
+            quality(self.quality),
+            quality(self.picked),
+        )
+    }
+
+    pub fn from_json(line: &str) -> Result<Self, String> {
+        let fields = parse_flat_object(line)?;
+        let get = |key: &str| -> Result<&Value, String> {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or(format!("missing field {key}"))
+        };
+
+        let version = get("version")?.as_number()?;
+        if version != FORMAT_VERSION as f64 {
+            return Err(format!("unsupported history version {version}"));
+        }
+
+        let quality = |key: &str| -> Result<Option<ChordQuality>, String> {
+            match get(key) {
+                Ok(Value::Null) | Err(_) => Ok(None),
+                Ok(value) => ChordQuality::parse(value.as_str()?).map(Some),
+            }
+        };
+        let note = |key: &str| -> Result<Note, String> {
+            let repr = get(key)?.as_str()?;
+            Note::parse_from_string(repr).map_err(|e| format!("{key}: {e}"))
+        };
+
+        Ok(Record {
+            timestamp: UNIX_EPOCH + Duration::from_millis(get("timestamp_ms")?.as_number()? as u64),
+            drill: match get("drill") {
+                Ok(value) => Drill::parse(value.as_str()?)?,
+                Err(_) => Drill::Intervals,
+            },
+            reference: note("reference")?,
+            target: note("target")?,
+            interval: Interval::parse_from_string(get("interval")?.as_str()?)?,
+            direction: Direction::parse_from_string(get("direction")?.as_str()?)?,
+            presentation: match get("presentation") {
+                Ok(value) => Presentation::parse_from_string(value.as_str()?)?,
+                Err(_) => Presentation::Melodic,
+            },
+            reference_success: get("reference_success")?.as_bool()?,
+            success: get("success")?.as_bool()?,
+            octaves: match get("octaves") {
+                Ok(value) => OctaveMatching::parse(value.as_str()?)?,
+                Err(_) => OctaveMatching::Tolerant,
+            },
+            detected_frequency: get("detected_frequency")?.as_optional_number()?,
+            cent_deviation: get("cent_deviation")?
+                .as_optional_number()?
+                .map(|c| c as CentDeviation),
+            response_time: Duration::from_millis(get("response_time_ms")?.as_number()? as u64),
+            quality: quality("quality")?,
+            picked: quality("picked")?,
+        })
+    }
+}
+
+/// `$XDG_DATA_HOME`, or the platform's usual spot for application data
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    let from_env = |key: &str| {
+        std::env::var_os(key)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+
+    if cfg!(target_os = "windows") {
+        return from_env("APPDATA");
+    }
+    if let Some(dir) = from_env("XDG_DATA_HOME") {
+        return Some(dir);
+    }
+    let home = from_env("HOME")?;
+    match cfg!(target_os = "macos") {
+        true => Some(home.join("Library").join("Application Support")),
+        false => Some(home.join(".local").join("share")),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    pub(crate) fn as_number(&self) -> Result<f64, String> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            _ => Err(format!("expected a number, got {self:?}")),
+        }
+    }
+
+    pub(crate) fn as_optional_number(&self) -> Result<Option<f64>, String> {
+        match self {
+            Value::Null => Ok(None),
+            _ => self.as_number().map(Some),
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(format!("expected a boolean, got {self:?}")),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Result<&str, String> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("expected a string, got {self:?}")),
+        }
+    }
+}
+
+/// For the strings written by hand, device names being free text
+pub(crate) fn escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Just enough JSON for the history, the calibrations and the picked devices: a single object of
+/// strings, numbers, booleans and nulls
+pub(crate) fn parse_flat_object(line: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = Vec::new();
+
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+    let parse_string = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        if chars.next() != Some('"') {
+            return Err(String::from("expected a string"));
+        }
+        let mut string = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match chars.next() {
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    _ => return Err(String::from("unsupported escape sequence")),
+                },
+                Some(c) => string.push(c),
+                None => return Err(String::from("unterminated string")),
+            }
+        }
+    };
+
+    if chars.next() != Some('{') {
+        return Err(String::from("expected an object"));
+    }
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(fields);
+    }
+
+    loop {
+        skip_whitespace(&mut chars);
+        let key = parse_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(format!("expected ':' after {key}"));
+        }
+        skip_whitespace(&mut chars);
+
+        let value = match chars.peek() {
+            Some('"') => Value::String(parse_string(&mut chars)?),
+            Some(_) => {
+                let mut token = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}') && !c.is_whitespace())
+                {
+                    token.push(c);
+                }
+                match token.as_str() {
+                    "null" => Value::Null,
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => Value::Number(
+                        token
+                            .parse()
+                            .map_err(|_| format!("invalid value for {key}: {token}"))?,
+                    ),
+                }
+            }
+            None => return Err(String::from("unexpected end of line")),
+        };
+        fields.push((key, value));
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(fields),
+            _ => return Err(String::from("expected ',' or '}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::interval::{BaseInterval, Quality};
+    use crate::music::NoteName;
+
+    fn record(success: bool) -> Record {
+        Record {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_697_640_000_123),
+            drill: Drill::Intervals,
+            reference: Note { name: NoteName::E, alteration: -1, octave: 4 },
+            target: Note { name: NoteName::G, alteration: 0, octave: 4 },
+            interval: Interval { base_interval: BaseInterval::Third, quality: Quality::Major },
+            direction: Direction::Up,
+            presentation: Presentation::Harmonic,
+            reference_success: true,
+            success,
+            octaves: OctaveMatching::Strict,
+            detected_frequency: success.then_some(392.5),
+            cent_deviation: success.then_some(-3),
+            response_time: Duration::from_millis(640),
+            quality: None,
+            picked: None,
+        }
+    }
+
+    #[test]
+    fn records_survive_a_round_trip() {
+        for success in [true, false] {
+            let record = record(success);
+            assert_eq!(Record::from_json(&record.to_json()), Ok(record));
+        }
+    }
+
+    #[test]
+    fn lines_without_a_presentation_are_melodic() {
+        let line = record(true)
+            .to_json()
+            .replace("\"presentation\":\"harmonic\",", "");
+        let record = Record::from_json(&line).unwrap();
+        assert_eq!(record.presentation, Presentation::Melodic);
+    }
+
+    #[test]
+    fn chord_qualities_are_kept() {
+        let record = Record {
+            drill: Drill::Chords,
+            quality: Some(ChordQuality::HalfDiminishedSeventh),
+            picked: Some(ChordQuality::MinorSeventh),
+            ..record(false)
+        };
+        assert_eq!(Record::from_json(&record.to_json()), Ok(record.clone()));
+
+        // older lines don't have them
+        let line = record.to_json().replace(
+            ",\"quality\":\"half-diminished seventh\",\"picked\":\"minor seventh\"",
+            "",
+        );
+        let record = Record::from_json(&line).unwrap();
+        assert_eq!((record.quality, record.picked), (None, None));
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let line = record(true)
+            .to_json()
+            .replace("\"version\":1", "\"version\":2");
+        assert!(Record::from_json(&line).is_err());
+    }
+
+    #[test]
+    fn history_file_append_and_load() {
+        let dir =
+            std::env::temp_dir().join(format!("interval_trainer_test_{}", std::process::id()));
+        let history = History::at(dir.join("history.jsonl"));
+
+        assert_eq!(history.load(), Ok(Vec::new()));
+        history.append(&record(true)).unwrap();
+        history.append(&record(false)).unwrap();
+        assert_eq!(history.load(), Ok(vec![record(true), record(false)]));
+
+        // a bad line loses itself, not the rest
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(history.path())
+            .unwrap();
+        writeln!(file, "{{\"version\":1,\"timestamp_ms\":").unwrap();
+        history.append(&record(true)).unwrap();
+        assert_eq!(
+            history.load(),
+            Ok(vec![record(true), record(false), record(true)])
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Transposing instruments
+//!
+//! Players of Bb, Eb and F instruments read a different note from the one they sound. The
+//! trainer works in written pitch, the notes it draws, names and records being the ones on the
+//! player's part, and only turns them into sounding pitch to play them and to listen for them.
+
+use std::fmt;
+
+use crate::audio::detection::HeardNote;
+use crate::audio::listen::ListenOutcome;
+use crate::music::interval::{BaseInterval, Quality};
+use crate::music::{Direction, Interval, Note, NoteRange, SimpleNote};
+use crate::render::clef::Clef;
+use crate::render::Staff;
+
+/// How an instrument reads music
+#[derive(Copy, Clone, Debug)]
+pub struct Instrument {
+    pub name: &'static str,
+    /// from the written note to the sounding one
+    pub transposition: Interval,
+    pub direction: Direction,
+    /// as written
+    pub range: NoteRange,
+    pub staff: Staff,
+}
+
+impl Instrument {
+    pub fn all() -> Vec<Instrument> {
+        let treble = Staff::Single(Clef::Treble);
+        let interval = |base_interval, quality| Interval { base_interval, quality };
+        let major_second = interval(BaseInterval::Second, Quality::Major);
+        let range = |bottom, top| NoteRange::from_str(bottom, top).unwrap();
+        let saxophone = range("Bb3", "F6");
+
+        vec![
+            Instrument {
+                name: "trumpet",
+                transposition: major_second,
+                direction: Direction::Down,
+                range: range("F#3", "C6"),
+                staff: treble,
+            },
+            Instrument {
+                name: "clarinet",
+                transposition: major_second,
+                direction: Direction::Down,
+                range: range("E3", "C6"),
+                staff: treble,
+            },
+            Instrument {
+                name: "soprano-sax",
+                transposition: major_second,
+                direction: Direction::Down,
+                range: saxophone,
+                staff: treble,
+            },
+            Instrument {
+                name: "alto-sax",
+                transposition: interval(BaseInterval::Sixth, Quality::Major),
+                direction: Direction::Down,
+                range: saxophone,
+                staff: treble,
+            },
+            Instrument {
+                name: "tenor-sax",
+                transposition: interval(BaseInterval::Ninth, Quality::Major),
+                direction: Direction::Down,
+                range: saxophone,
+                staff: treble,
+            },
+            Instrument {
+                name: "baritone-sax",
+                transposition: interval(BaseInterval::Thirteenth, Quality::Major),
+                direction: Direction::Down,
+                range: saxophone,
+                staff: treble,
+            },
+            Instrument {
+                name: "horn",
+                transposition: interval(BaseInterval::Fifth, Quality::Perfect),
+                direction: Direction::Down,
+                range: range("G3", "C6"),
+                staff: treble,
+            },
+            Instrument {
+                name: "guitar",
+                transposition: interval(BaseInterval::Octave, Quality::Perfect),
+                direction: Direction::Down,
+                range: range("E3", "B5"),
+                staff: treble,
+            },
+        ]
+    }
+
+    /// From the written note to the sounding one, negative for an instrument sounding lower
+    pub fn semitones(&self) -> i8 {
+        match self.direction {
+            Direction::Up => self.transposition.size_i8(),
+            Direction::Down => -self.transposition.size_i8(),
+        }
+    }
+
+    pub fn sounding(&self, written: Note) -> Note {
+        written.shift(self.transposition, self.direction)
+    }
+
+    pub fn written(&self, sounding: SimpleNote) -> SimpleNote {
+        sounding.shift(-self.semitones())
+    }
+
+    /// A range at concert pitch, e.g. a `--range` preset, as the player would read it
+    pub fn written_range(&self, sounding: NoteRange) -> NoteRange {
+        NoteRange { bottom: self.written(sounding.bottom), top: self.written(sounding.top) }
+    }
+
+    /// The heard note as the player would read it, its frequency and cents are left alone
+    pub fn written_heard(&self, heard: HeardNote) -> HeardNote {
+        HeardNote { note: self.written(heard.note), ..heard }
+    }
+
+    /// Back to written pitch, for an outcome of listening for a sounding note
+    pub fn written_outcome(&self, outcome: ListenOutcome) -> ListenOutcome {
+        ListenOutcome {
+            target: self.written(outcome.target),
+            heard: outcome.heard.map(|heard| self.written_heard(heard)),
+            ..outcome
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        Instrument::all()
+            .into_iter()
+            .find(|instrument| instrument.name == string)
+            .ok_or(format!(
+                "invalid instrument: {string} (expected {})",
+                Instrument::all()
+                    .iter()
+                    .map(|instrument| instrument.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+    }
+}
+
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sounding(instrument: &str, written: &str) -> String {
+        let instrument = Instrument::parse(instrument).unwrap();
+        let written = Note::parse_from_string(written).unwrap();
+        instrument.sounding(written).to_string()
+    }
+
+    #[test]
+    fn written_and_sounding_pitch() {
+        assert_eq!(sounding("trumpet", "D5"), "C5");
+        assert_eq!(sounding("alto-sax", "A4"), "C4");
+        assert_eq!(sounding("tenor-sax", "F#4"), "E3");
+        assert_eq!(sounding("baritone-sax", "C5"), "Eb3");
+        assert_eq!(sounding("horn", "C5"), "F4");
+
+        let alto_sax = Instrument::parse("alto-sax").unwrap();
+        let c4 = Note::parse_from_string("C4").unwrap().to_simple();
+        assert_eq!(alto_sax.written(c4), SimpleNote::new(69));
+    }
+}
@@ -1,64 +1,1306 @@
-use std::{sync::mpsc::Sender, time::Duration};
+pub mod scheduler;
 
-use crate::music::{Direction, Interval, Note, NoteRange};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::{fmt, time::Duration, time::Instant, time::SystemTime};
 
-use crate::audio;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::audio::backend::{
+    AudioBackend, DeviceBackend, LoopbackBackend, NullBackend, SoundEffect,
+};
+use crate::audio::detection::{DetectionSettings, HeardNote};
+use crate::audio::devices::{self, DeviceChoice, DeviceChoices, DEVICES_FILE};
+use crate::audio::listen::CentDeviation;
+use crate::audio::listen::{ListenOutcome, PitchReading};
+use crate::calibration::{Calibration, CalibrationStep, Calibrations, CALIBRATION_FILE};
+use crate::history::{data_dir, History, Record, APP_DIR};
+use crate::music::{
+    Chord, ChordQuality, ChordTone, Direction, Interval, Key, Melody, NamingPolicy, Note,
+    NoteRange, Presentation, ScaleDegree, SimpleNote,
+};
+use crate::render::Staff;
+use crate::settings::{AudioBackendKind, ChordAnswerMode, Drill, HistoryLocation, Settings};
+use crate::stats::Statistics;
+
+use scheduler::Scheduler;
+
+const MELODY_NOTE_LENGTH: Duration = Duration::from_millis(600);
 
 pub struct IntervalTrainer {
     pub scene: Scene,
     range: NoteRange,
-    sample_rate: u16,
+    settings: Settings,
+    backend: Arc<dyn AudioBackend>,
+    playback_tx: Sender<()>,
+    playback_rx: Receiver<()>,
+    pitch_detection_tx: Sender<ListenOutcome>,
+    pitch_detection_rx: Receiver<ListenOutcome>,
+    /// from the audio threads, when a device fails or falls back to the default one
+    errors_tx: Sender<String>,
+    errors_rx: Receiver<String>,
+    /// the last audio error, shown until the next exercise
+    notice: Option<String>,
+    scheduler: Scheduler,
+    history: Option<History>,
+    records: Vec<Record>,
+    statistics: Statistics,
+    /// what the statistics are limited to, all presentations when `None`
+    statistics_presentation: Option<Presentation>,
+    exercise: Option<Exercise>,
+    /// the notes sung so far in the current exercise
+    heard: Vec<ListenOutcome>,
+    /// from the audio thread, while listening or tuning
+    readings: Option<Receiver<PitchReading>>,
+    /// what was heard while listening for each note of the current exercise
+    trace: Vec<Vec<PitchReading>>,
+    /// the last pitch heard by the tuner, `None` during silence
+    tuning: Option<HeardNote>,
+    calibrations: Option<Calibrations>,
+    /// what was heard since calibration started
+    calibration: Vec<PitchReading>,
+    /// once calibration is over
+    suggestion: Option<Result<Calibration, String>>,
+    /// where the picked devices are kept, next to the history
+    devices_path: Option<PathBuf>,
+    /// the devices picked in the menu, this session or before, unlike the ones from the command
+    /// line they're kept for next time
+    picked_devices: DeviceChoices,
+    /// while picking devices
+    device_menu: Option<DeviceMenu>,
+    answer: Option<Answer>,
+    /// of the current exercise
+    key: Option<Key>,
+    /// of the current exercise
+    chord: Option<Chord>,
+    /// when the chord qualities were offered
+    choosing_since: Instant,
+}
+
+/// The devices to pick from, listed once when the menu is opened
+#[derive(Clone, Debug)]
+pub struct DeviceMenu {
+    pub inputs: Vec<DeviceChoice>,
+    pub outputs: Vec<DeviceChoice>,
+    /// which list the number keys pick from
+    pub picking_output: bool,
+}
+
+/// How the last exercise went
+#[derive(Clone, Debug)]
+pub struct Answer {
+    pub interval: Interval,
+    pub direction: Direction,
+    pub outcome: ListenOutcome,
+    /// from the reference to the note that was heard, regardless of octave
+    pub sung_interval: Option<Interval>,
+    pub degree: Option<DegreeAnswer>,
+    pub chord: Option<ChordAnswer>,
+    pub melody: Option<MelodyAnswer>,
+}
+
+/// How a scale degree exercise went
+#[derive(Copy, Clone, Debug)]
+pub struct DegreeAnswer {
+    pub key: Key,
+    pub expected: ScaleDegree,
+    /// of the note that was heard
+    pub sung: Option<ScaleDegree>,
+}
+
+/// How a chord exercise went
+#[derive(Copy, Clone, Debug)]
+pub struct ChordAnswer {
+    pub chord: Chord,
+    /// the chord tone to sing, `None` when the quality is picked with the keyboard
+    pub tone: Option<ChordTone>,
+    pub picked: Option<ChordQuality>,
+}
+
+/// How a melody was sung back
+#[derive(Clone, Debug)]
+pub struct MelodyAnswer {
+    pub melody: Melody,
+    /// one per note
+    pub heard: Vec<ListenOutcome>,
+}
+
+/// Two notes to sing back, or in a scale degree exercise the tonic and a note of the key, or in
+/// a melody exercise its first and last notes
+#[derive(Clone, Debug)]
+pub struct Exercise {
+    pub reference: Note,
+    pub mystery_note: Note,
+    pub interval: Interval,
+    pub direction: Direction,
+    pub presentation: Presentation,
+    /// the key set by a cadence before the mystery note, only the latter is sung back
+    pub cadence: Option<Key>,
+    /// played instead of the two notes, the reference being its bass
+    pub chord: Option<Chord>,
+    /// played and sung back instead of the two notes
+    pub melody: Option<Melody>,
 }
 
 impl IntervalTrainer {
-    pub fn init(range: NoteRange) -> Self {
-        const SAMPLE_RATE: u16 = 44_100;
-        Self { scene: Scene::Idle, range, sample_rate: SAMPLE_RATE }
+    pub fn init(range: NoteRange, mut settings: Settings) -> Self {
+        // devices from the command line win over the ones picked last time
+        let devices_path = match &settings.history {
+            HistoryLocation::Default => data_dir().map(|dir| dir.join(APP_DIR).join(DEVICES_FILE)),
+            HistoryLocation::At(path) => Some(path.with_file_name(DEVICES_FILE)),
+            HistoryLocation::Disabled => None,
+        };
+        let picked_devices = match devices_path.as_deref().map(DeviceChoices::load) {
+            Some(Ok(saved)) => saved,
+            Some(Err(e)) => {
+                eprintln!("could not load the picked devices: {e}");
+                DeviceChoices::default()
+            }
+            None => DeviceChoices::default(),
+        };
+        settings.devices.input = settings
+            .devices
+            .input
+            .take()
+            .or(picked_devices.input.clone());
+        settings.devices.output = settings
+            .devices
+            .output
+            .take()
+            .or(picked_devices.output.clone());
+
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let backend = open_backend(&settings, &errors_tx).unwrap_or_else(|e| {
+            eprintln!("running without sound: {e}");
+            Arc::new(NullBackend)
+        });
+
+        let history = match &settings.history {
+            HistoryLocation::Default => History::open_default()
+                .map_err(|e| eprintln!("practice history disabled: {e}"))
+                .ok(),
+            HistoryLocation::At(path) => Some(History::at(path)),
+            HistoryLocation::Disabled => None,
+        };
+
+        // kept next to the history
+        let calibrations = match &settings.history {
+            HistoryLocation::Default => Calibrations::open_default()
+                .map_err(|e| eprintln!("calibrations disabled: {e}"))
+                .ok(),
+            HistoryLocation::At(path) => {
+                Some(Calibrations::at(path.with_file_name(CALIBRATION_FILE)))
+            }
+            HistoryLocation::Disabled => None,
+        };
+        if let Some(calibration) = saved_calibration(&calibrations, &*backend, &settings) {
+            settings.detection = calibration.apply(settings.detection);
+        }
+
+        let records = match history.as_ref().map(History::load) {
+            Some(Ok(records)) => records,
+            Some(Err(e)) => {
+                eprintln!("could not load the practice history: {e}");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        let mut scheduler = Scheduler::new();
+        for record in records.iter().filter(|r| r.drill == Drill::Intervals) {
+            scheduler.record(
+                record.interval,
+                record.direction,
+                record.presentation,
+                record.success,
+                record.response_time,
+            );
+        }
+
+        let (playback_tx, playback_rx) = mpsc::channel();
+        let (pitch_detection_tx, pitch_detection_rx) = mpsc::channel();
+
+        Self {
+            scene: Scene::Idle,
+            range,
+            key: settings.key,
+            chord: None,
+            choosing_since: Instant::now(),
+            settings,
+            backend,
+            playback_tx,
+            playback_rx,
+            pitch_detection_tx,
+            pitch_detection_rx,
+            errors_tx,
+            errors_rx,
+            notice: None,
+            scheduler,
+            history,
+            statistics: Statistics::from_records(&records),
+            statistics_presentation: None,
+            records,
+            exercise: None,
+            heard: Vec::new(),
+            readings: None,
+            trace: Vec::new(),
+            tuning: None,
+            calibrations,
+            calibration: Vec::new(),
+            suggestion: None,
+            devices_path,
+            picked_devices,
+            device_menu: None,
+            answer: None,
+        }
+    }
+
+    /// Plays a new exercise, does nothing unless idle
+    pub fn start(&mut self) {
+        if let Scene::Idle = self.scene {
+            self.notice = None;
+            self.start_playback();
+            self.scene = Scene::PlayingSound;
+        }
+    }
+
+    /// From idle to the tuner and back
+    pub fn toggle_tuner(&mut self) {
+        match self.scene {
+            Scene::Idle => {
+                self.track_pitch(self.settings.detection);
+                self.tuning = None;
+                self.scene = Scene::Tuner;
+            }
+            Scene::Tuner => {
+                // hanging up stops the audio thread
+                self.readings = None;
+                self.scene = Scene::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// From idle to calibrating the input, or back without saving anything. Backends without an
+    /// input device have nothing to calibrate
+    pub fn toggle_calibration(&mut self) {
+        match self.scene {
+            Scene::Idle if self.backend.input_device().is_none() => {
+                self.notice = Some(String::from("no input device to calibrate"));
+            }
+            Scene::Idle => {
+                self.notice = None;
+                self.track_pitch(Calibration::listening(self.settings.detection));
+                self.calibration.clear();
+                self.suggestion = None;
+                self.scene = Scene::Calibrating;
+            }
+            Scene::Calibrating => {
+                self.readings = None;
+                self.scene = Scene::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Uses the suggested thresholds from now on, and keeps them for the input device
+    pub fn accept_calibration(&mut self) {
+        let (Scene::Calibrating, Some(Ok(calibration))) = (self.scene, &self.suggestion) else {
+            return;
+        };
+        self.settings.detection = calibration.apply(self.settings.detection);
+        if let (Some(calibrations), false) = (&self.calibrations, calibration.device.is_empty()) {
+            if let Err(e) = calibrations.save(calibration) {
+                eprintln!("could not save the calibration: {e}");
+            }
+        }
+        self.scene = Scene::Idle;
+    }
+
+    /// From idle to picking the sound devices and back
+    pub fn toggle_devices(&mut self) {
+        match self.scene {
+            Scene::Idle => {
+                self.device_menu = Some(DeviceMenu {
+                    inputs: devices::input_devices(),
+                    outputs: devices::output_devices(),
+                    picking_output: false,
+                });
+                self.notice = None;
+                self.scene = Scene::Devices;
+            }
+            Scene::Devices => {
+                self.device_menu = None;
+                self.scene = Scene::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Between picking the input and the output device
+    pub fn switch_device_list(&mut self) {
+        if let Some(menu) = &mut self.device_menu {
+            menu.picking_output = !menu.picking_output;
+        }
+    }
+
+    /// The default device for 0, then the listed ones. The backend is opened again on the new
+    /// device, which is kept for next time along with the ones picked before
+    pub fn pick_device(&mut self, index: usize) {
+        let (Scene::Devices, Some(menu)) = (self.scene, &self.device_menu) else {
+            return;
+        };
+        let list = match menu.picking_output {
+            true => &menu.outputs,
+            false => &menu.inputs,
+        };
+        let picked = match index {
+            0 => None,
+            _ => match list.get(index - 1) {
+                Some(device) => Some(device.clone()),
+                None => return,
+            },
+        };
+        match menu.picking_output {
+            true => {
+                self.picked_devices.output = picked.clone();
+                self.settings.devices.output = picked;
+            }
+            false => {
+                self.picked_devices.input = picked.clone();
+                self.settings.devices.input = picked;
+            }
+        }
+
+        self.backend = open_backend(&self.settings, &self.errors_tx).unwrap_or_else(|e| {
+            self.notice = Some(format!("running without sound: {e}"));
+            Arc::new(NullBackend)
+        });
+        if let Some(path) = &self.devices_path {
+            if let Err(e) = self.picked_devices.save(path) {
+                eprintln!("could not save the picked devices: {e}");
+            }
+        }
+        // the thresholds of the previous input don't fit the new one
+        if !menu.picking_output && !self.settings.thresholds_given {
+            self.settings.detection =
+                match saved_calibration(&self.calibrations, &*self.backend, &self.settings) {
+                    Some(calibration) => calibration.apply(self.settings.detection),
+                    None => DetectionSettings {
+                        power_threshold: DetectionSettings::DEFAULT.power_threshold,
+                        clarity_threshold: DetectionSettings::DEFAULT.clarity_threshold,
+                        ..self.settings.detection
+                    },
+                };
+        }
+    }
+
+    fn track_pitch(&mut self, settings: DetectionSettings) {
+        let backend = self.backend.clone();
+        let errors_tx = self.errors_tx.clone();
+        let (readings_tx, readings_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(e) = backend.track_pitch(settings, readings_tx) {
+                errors_tx.send(format!("could not listen: {e}")).ok();
+            }
+        });
+        self.readings = Some(readings_rx);
+    }
+
+    /// Moves on to the next scene once the audio thread is done with the current one
+    pub fn update(&mut self) {
+        for error in self.errors_rx.try_iter() {
+            eprintln!("{error}");
+            self.notice = Some(error);
+        }
+
+        let readings: Vec<PitchReading> = match &self.readings {
+            Some(readings) => readings
+                .try_iter()
+                .map(|r| self.written_reading(r))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        match self.scene {
+            Scene::Tuner => {
+                if let Some(reading) = readings.last() {
+                    self.tuning = reading.heard;
+                }
+            }
+            Scene::Calibrating => {
+                self.calibration.extend(readings);
+                let done = self
+                    .calibration
+                    .last()
+                    .map(|reading| CalibrationStep::at(reading.at))
+                    == Some(CalibrationStep::Done);
+                if done && self.suggestion.is_none() {
+                    self.readings = None;
+                    // named when calibration started, unless it has gone since, in which case
+                    // it's calibrated for the session only
+                    let device = self.backend.input_device().unwrap_or_default();
+                    self.suggestion = Some(Calibration::suggest(
+                        device,
+                        self.settings.detection.buffer_size,
+                        &self.calibration,
+                    ));
+                }
+            }
+            Scene::PlayingSound => {
+                if let Ok(()) = self.playback_rx.try_recv() {
+                    self.scene = match self.to_sing().first() {
+                        Some(&note) => {
+                            self.listen_for(note);
+                            Scene::Listening(0)
+                        }
+                        // the quality of the chord is picked instead
+                        None => {
+                            self.choosing_since = Instant::now();
+                            Scene::Choosing
+                        }
+                    };
+                }
+            }
+            Scene::Listening(index) => {
+                if let Some(trace) = self.trace.get_mut(index) {
+                    trace.extend(readings);
+                }
+                if let Ok(outcome) = self.pitch_detection_rx.try_recv() {
+                    self.ding_or_bad_ding(outcome.success);
+                    self.heard.push(outcome);
+                    self.scene = match self.to_sing().get(index + 1) {
+                        Some(&note) => {
+                            self.listen_for(note);
+                            Scene::Listening(index + 1)
+                        }
+                        None => {
+                            self.record_answer(None);
+                            Scene::Concluding
+                        }
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Answers a chord exercise with one of `chord_qualities`, does nothing unless choosing
+    pub fn pick_quality(&mut self, index: usize) {
+        let Scene::Choosing = self.scene else {
+            return;
+        };
+        let (Some(&picked), Some(exercise)) = (self.chord_qualities().get(index), &self.exercise)
+        else {
+            return;
+        };
+        let Some(chord) = exercise.chord else {
+            return;
+        };
+
+        let outcome = ListenOutcome {
+            target: exercise.mystery_note.to_simple(),
+            heard: None,
+            success: picked == chord.quality,
+            elapsed: self.choosing_since.elapsed(),
+            matching: self.settings.octaves,
+        };
+        self.ding_or_bad_ding(outcome.success);
+        self.heard.push(outcome);
+        self.record_answer(Some(picked));
+        self.scene = Scene::Concluding;
+    }
+
+    /// The qualities to pick from, in the order of the number keys
+    pub fn chord_qualities(&self) -> Vec<ChordQuality> {
+        self.settings.chords.qualities()
     }
 
-    pub fn start_playback(&self, playback_tx: Sender<()>) -> (Note, Note) {
-        let (reference, mystery_note) = self.choose_notes();
+    /// What the user is expected to do, for the drills where it isn't obvious
+    pub fn prompt(&self) -> Option<String> {
+        match (self.scene, &self.suggestion) {
+            (Scene::Tuner, _) => return Some(String::from("Press T to go back")),
+            (Scene::Statistics, _) => {
+                let shown = match self.statistics_presentation {
+                    None => "melodic and harmonic intervals",
+                    Some(Presentation::Melodic) => "melodic intervals only",
+                    Some(Presentation::Harmonic) => "harmonic intervals only",
+                };
+                return Some(format!("{shown}, P to switch, S to go back"));
+            }
+            (Scene::Devices, _) => {
+                return Some(String::from(
+                    "Number keys to pick, Tab to switch between input and output, D to go back",
+                ))
+            }
+            (Scene::Calibrating, None) => {
+                let step = self
+                    .calibration
+                    .last()
+                    .map(|reading| CalibrationStep::at(reading.at));
+                return Some(String::from(match step {
+                    None | Some(CalibrationStep::Quiet) => "Stay quiet for a few seconds",
+                    _ => "Now play or sing a long note",
+                }));
+            }
+            (Scene::Calibrating, Some(Ok(calibration))) => {
+                return Some(format!(
+                    "Room {:.2}, you {:.1}: power threshold {:.2}, clarity {:.2}. \
+                     Enter to save, C to cancel",
+                    calibration.noise_floor,
+                    calibration.level,
+                    calibration.power_threshold,
+                    calibration.clarity_threshold
+                ))
+            }
+            (Scene::Calibrating, Some(Err(e))) => {
+                return Some(format!("Calibration failed, {e}. C to go back"))
+            }
+            _ => {}
+        }
+        let drill = self.exercise.as_ref().map(Exercise::drill)?;
+        match (self.scene, drill, self.settings.chord_answer) {
+            (Scene::Choosing, _, _) => Some(
+                self.chord_qualities()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, quality)| format!("{} {quality}", i + 1))
+                    .collect::<Vec<_>>()
+                    .join("  "),
+            ),
+            (Scene::Listening(_), Drill::Chords, ChordAnswerMode::Sing(tone)) => {
+                Some(format!("Sing the {tone}"))
+            }
+            (Scene::Listening(index), Drill::Melodies, _) => {
+                Some(format!("Note {} of {}", index + 1, self.to_sing().len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The notes of the current exercise to sing back, in order
+    fn to_sing(&self) -> Vec<Note> {
+        let Some(exercise) = &self.exercise else {
+            return Vec::new();
+        };
+        match (exercise.drill(), self.settings.chord_answer) {
+            (Drill::Intervals, _) => vec![exercise.reference, exercise.mystery_note],
+            // the tonic is only heard in the cadence, the bass in the chord
+            (Drill::ScaleDegrees, _) | (Drill::Chords, ChordAnswerMode::Sing(_)) => {
+                vec![exercise.mystery_note]
+            }
+            (Drill::Chords, ChordAnswerMode::Keyboard) => Vec::new(),
+            (Drill::Melodies, _) => exercise
+                .melody
+                .as_ref()
+                .map(|melody| melody.notes.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The notes of the current exercise to draw, `None` for the ones not to be revealed yet
+    pub fn shown_notes(&self) -> Vec<Option<Note>> {
+        let Some(exercise) = &self.exercise else {
+            return Vec::new();
+        };
+        match (self.scene, &exercise.melody) {
+            (Scene::Idle | Scene::Statistics, _) => Vec::new(),
+            (Scene::Concluding, Some(melody)) => melody.notes.iter().copied().map(Some).collect(),
+            // revealed as they're sung
+            (_, Some(melody)) => melody
+                .notes
+                .iter()
+                .enumerate()
+                .map(|(i, &note)| (i < self.heard.len()).then_some(note))
+                .collect(),
+            (Scene::Concluding, None) => {
+                vec![Some(exercise.reference), Some(exercise.mystery_note)]
+            }
+            (_, None) => vec![Some(exercise.reference), None],
+        }
+    }
+
+    fn start_playback(&mut self) {
+        self.choose_notes();
+        let Some(exercise) = self.exercise.clone() else {
+            return;
+        };
         let note_length = Duration::from_millis(1000);
-        audio::synth::play_notes_in_thread(
+        let backend = self.backend.clone();
+        let playback_tx = self.playback_tx.clone();
+        let errors_tx = self.errors_tx.clone();
+        let sounding = self.sounding();
+
+        std::thread::spawn(move || {
+            let played = match exercise {
+                Exercise { cadence: Some(key), reference, mystery_note, .. } => {
+                    let cadence = key
+                        .cadence(reference)
+                        .map(|chord| chord.into_iter().map(sounding).collect());
+                    backend
+                        .play_cadence(&cadence, note_length)
+                        .and_then(|()| backend.play_note(sounding(mystery_note), note_length))
+                }
+                Exercise { chord: Some(chord), .. } => {
+                    let notes: Vec<Note> = chord.notes().into_iter().map(sounding).collect();
+                    backend.play_chord(&notes, 2 * note_length)
+                }
+                Exercise { melody: Some(melody), .. } => melody
+                    .notes
+                    .iter()
+                    .try_for_each(|&note| backend.play_note(sounding(note), MELODY_NOTE_LENGTH)),
+                Exercise { reference, mystery_note, presentation, .. } => backend.play_notes(
+                    sounding(reference),
+                    sounding(mystery_note),
+                    presentation,
+                    note_length,
+                ),
+            };
+            if let Err(e) = played {
+                errors_tx
+                    .send(format!("could not play the exercise: {e}"))
+                    .ok();
+            }
+            playback_tx.send(()).ok();
+        });
+    }
+
+    /// Exercises are in written pitch, they're played and listened for at concert pitch
+    fn sounding(&self) -> impl Fn(Note) -> Note + Copy + Send + 'static {
+        let instrument = self.settings.instrument;
+        move |note| match instrument {
+            Some(instrument) => instrument.sounding(note),
+            None => note,
+        }
+    }
+
+    /// As the player would read it
+    fn written_reading(&self, reading: PitchReading) -> PitchReading {
+        match self.settings.instrument {
+            Some(instrument) => PitchReading {
+                heard: reading.heard.map(|heard| instrument.written_heard(heard)),
+                ..reading
+            },
+            None => reading,
+        }
+    }
+
+    /// A failure to listen counts as nothing heard
+    fn listen_for(&mut self, note: Note) {
+        let backend = self.backend.clone();
+        let pitch_detection_tx = self.pitch_detection_tx.clone();
+        let errors_tx = self.errors_tx.clone();
+        // a new channel for every note, so that late readings can't end up with the next one
+        let (readings_tx, readings_rx) = mpsc::channel();
+        self.readings = Some(readings_rx);
+        self.trace.push(Vec::new());
+        let matching = self.settings.octaves;
+        let detection = self.settings.detection;
+        let instrument = self.settings.instrument;
+        let target = self.sounding()(note).to_simple();
+
+        std::thread::spawn(move || {
+            let outcome = backend
+                .listen_for_note(target, matching, detection, readings_tx)
+                .map(|outcome| match instrument {
+                    Some(instrument) => instrument.written_outcome(outcome),
+                    None => outcome,
+                })
+                .unwrap_or_else(|e| {
+                    errors_tx.send(format!("could not listen: {e}")).ok();
+                    ListenOutcome {
+                        target: note.to_simple(),
+                        heard: None,
+                        success: false,
+                        elapsed: detection.window,
+                        matching,
+                    }
+                });
+            pitch_detection_tx.send(outcome).ok();
+        });
+    }
+
+    /// Feeds the outcome of the last note to the scheduler, and every sung note to the practice
+    /// history
+    fn record_answer(&mut self, picked: Option<ChordQuality>) {
+        let (Some(exercise), Some(&outcome)) = (self.exercise.clone(), self.heard.last()) else {
+            return;
+        };
+        if exercise.drill() == Drill::Intervals {
+            self.scheduler.record(
+                exercise.interval,
+                exercise.direction,
+                exercise.presentation,
+                outcome.success,
+                outcome.elapsed,
+            );
+        }
+
+        let sung_interval = outcome.heard.map(|heard| {
+            let semitones = heard.note.get_i8() - exercise.reference.to_simple().get_i8();
+            match exercise.direction {
+                Direction::Up => Interval::from_semitones(semitones),
+                Direction::Down => Interval::from_semitones(-semitones),
+            }
+        });
+        let degree = exercise.cadence.map(|key| {
+            let expected = ScaleDegree::of(exercise.mystery_note, &key);
+            let target = exercise.mystery_note.to_simple().get_i8();
+            DegreeAnswer {
+                key,
+                expected,
+                // the right pitch is named as asked, C# as di or Db as ra
+                sung: outcome.heard.map(|heard| {
+                    match (heard.note.get_i8() - target).rem_euclid(12) {
+                        0 => expected,
+                        _ => ScaleDegree::of(key.spell(heard.note), &key),
+                    }
+                }),
+            }
+        });
+        let chord = exercise.chord.map(|chord| ChordAnswer {
+            chord,
+            tone: match self.settings.chord_answer {
+                ChordAnswerMode::Keyboard => None,
+                ChordAnswerMode::Sing(tone) => Some(tone),
+            },
+            picked,
+        });
+        let melody = exercise
+            .melody
+            .clone()
+            .map(|melody| MelodyAnswer { melody, heard: self.heard.clone() });
+        self.answer = Some(Answer {
+            interval: exercise.interval,
+            direction: exercise.direction,
+            outcome,
+            sung_interval,
+            degree,
+            chord,
+            melody,
+        });
+
+        let timestamp = SystemTime::now();
+        let records = match &exercise.melody {
+            // one per step, from the note before
+            Some(melody) => (1..melody.notes.len())
+                .map(|i| {
+                    let (from, to) = (melody.notes[i - 1], melody.notes[i]);
+                    let semitones = to.to_simple().get_i8() - from.to_simple().get_i8();
+                    let outcome = self.heard[i];
+                    Record {
+                        timestamp,
+                        drill: Drill::Melodies,
+                        reference: from,
+                        target: to,
+                        interval: Interval::between(from, to)
+                            .unwrap_or_else(|| Interval::from_semitones(semitones.abs())),
+                        direction: match semitones < 0 {
+                            true => Direction::Down,
+                            false => Direction::Up,
+                        },
+                        presentation: Presentation::Melodic,
+                        reference_success: self.heard[i - 1].success,
+                        success: outcome.success,
+                        octaves: outcome.matching,
+                        detected_frequency: outcome.heard.map(|h| h.frequency),
+                        cent_deviation: outcome.heard.map(|h| h.cent_deviation),
+                        response_time: outcome.elapsed,
+                        quality: None,
+                        picked: None,
+                    }
+                })
+                .collect(),
+            None => vec![Record {
+                timestamp,
+                drill: exercise.drill(),
+                reference: exercise.reference,
+                target: exercise.mystery_note,
+                interval: exercise.interval,
+                direction: exercise.direction,
+                presentation: exercise.presentation,
+                // only the interval drill has the reference sung first
+                reference_success: self.heard.len() > 1 && self.heard[0].success,
+                success: outcome.success,
+                octaves: outcome.matching,
+                detected_frequency: outcome.heard.map(|h| h.frequency),
+                cent_deviation: outcome.heard.map(|h| h.cent_deviation),
+                response_time: outcome.elapsed,
+                quality: exercise.chord.map(|chord| chord.quality),
+                picked,
+            }],
+        };
+        for record in records {
+            if let Some(history) = &self.history {
+                if let Err(e) = history.append(&record) {
+                    eprintln!("could not save to the practice history: {e}");
+                }
+            }
+            self.records.push(record);
+        }
+        self.statistics = Statistics::presented(&self.records, self.statistics_presentation);
+    }
+
+    pub fn answer(&self) -> Option<&Answer> {
+        self.answer.as_ref()
+    }
+
+    /// The note heard by the tuner, spelled as the exercises would be but never at random
+    pub fn tuning(&self) -> Option<(Note, HeardNote)> {
+        let heard = self.tuning?;
+        let policy = match self.settings.spelling.policy(self.settings.key) {
+            NamingPolicy::Random => NamingPolicy::FewestAccidentals,
+            policy => policy,
+        };
+        Some((heard.note.spell(policy), heard))
+    }
+
+    pub fn detection(&self) -> DetectionSettings {
+        self.settings.detection
+    }
+
+    pub fn devices(&self) -> &DeviceChoices {
+        &self.settings.devices
+    }
+
+    pub fn device_menu(&self) -> Option<&DeviceMenu> {
+        self.device_menu.as_ref()
+    }
+
+    /// The last thing that went wrong with the sound devices
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_deref()
+    }
+
+    /// Of the last buffer heard while calibrating
+    pub fn input_level(&self) -> Option<f32> {
+        self.calibration.last().map(|reading| reading.power)
+    }
+
+    /// For every note sung in the current exercise, the note it should have sounded like and
+    /// what was heard while listening for it
+    pub fn pitch_trace(&self) -> Vec<(SimpleNote, &[PitchReading])> {
+        self.heard
+            .iter()
+            .zip(&self.trace)
+            .map(|(outcome, trace)| {
+                let expected = outcome.target.shift(outcome.matching.transposition());
+                (expected, trace.as_slice())
+            })
+            .collect()
+    }
+
+    /// The staff from the settings, or the one that best fits the range
+    pub fn staff(&self) -> Staff {
+        self.settings
+            .staff
+            .unwrap_or_else(|| Staff::for_range(&self.range))
+    }
+
+    /// Of the current exercise, a scale degree exercise always has one
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+
+    /// Of the current exercise, in a chord drill
+    pub fn chord(&self) -> Option<Chord> {
+        self.chord
+    }
+
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    /// From every presentation to melodic intervals only, harmonic ones only, and back
+    pub fn cycle_statistics_presentation(&mut self) {
+        self.statistics_presentation = match self.statistics_presentation {
+            None => Some(Presentation::Melodic),
+            Some(Presentation::Melodic) => Some(Presentation::Harmonic),
+            Some(Presentation::Harmonic) => None,
+        };
+        self.statistics = Statistics::presented(&self.records, self.statistics_presentation);
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    fn ding_or_bad_ding(&self, success: bool) {
+        match success {
+            true => self.backend.play_effect(SoundEffect::Ding),
+            false => self.backend.play_effect(SoundEffect::Wrong),
+        }
+    }
+
+    fn choose_notes(&mut self) {
+        let exercise = Exercise::draw(&self.range, &self.settings, &self.scheduler);
+        self.key = exercise
+            .cadence
+            .or(exercise.melody.as_ref().map(|melody| melody.key))
+            .or(self.settings.key);
+        self.chord = exercise.chord;
+        self.exercise = Some(exercise);
+        self.heard.clear();
+        self.trace.clear();
+    }
+}
+
+/// Warnings about devices falling back to the default ones go to `errors_tx`, to be shown
+fn open_backend(
+    settings: &Settings,
+    errors_tx: &Sender<String>,
+) -> Result<Arc<dyn AudioBackend>, String> {
+    const SAMPLE_RATE: u32 = 44_100;
+
+    Ok(match settings.audio {
+        AudioBackendKind::Device => Arc::new(DeviceBackend::open(
+            SAMPLE_RATE,
+            settings.timbre,
+            settings.devices.clone(),
+            errors_tx.clone(),
+        )?),
+        AudioBackendKind::Null => Arc::new(NullBackend),
+        AudioBackendKind::Loopback => Arc::new(LoopbackBackend::new(SAMPLE_RATE, settings.timbre)),
+    })
+}
+
+/// The calibration of the backend's input device, unless thresholds were given on the command
+/// line
+fn saved_calibration(
+    calibrations: &Option<Calibrations>,
+    backend: &dyn AudioBackend,
+    settings: &Settings,
+) -> Option<Calibration> {
+    match (calibrations, backend.input_device()) {
+        (Some(calibrations), Some(device)) if !settings.thresholds_given => {
+            calibrations.find(&device).unwrap_or_else(|e| {
+                eprintln!("could not load the calibrations: {e}");
+                None
+            })
+        }
+        _ => None,
+    }
+}
+
+impl Exercise {
+    /// A new exercise within the range, the interval being picked by the scheduler
+    pub fn draw(range: &NoteRange, settings: &Settings, scheduler: &Scheduler) -> Self {
+        match settings.drill {
+            Drill::ScaleDegrees => return Exercise::draw_scale_degree(range, settings),
+            Drill::Chords => return Exercise::draw_chord(range, settings),
+            Drill::Melodies => return Exercise::draw_melody(range, settings),
+            Drill::Intervals => {}
+        }
+
+        let direction = settings.direction.pick();
+        let presentation = settings.presentation.pick();
+        let interval = scheduler
+            .choose(&settings.intervals, direction, presentation, |interval| {
+                range.fits(interval.size_i8())
+            })
+            .expect("Settings::from_args checks that an interval fits in the range");
+
+        let new_range = match direction {
+            Direction::Up => range.crop_top(interval.size_i8()),
+            Direction::Down => range.crop_bottom(interval.size_i8()),
+        }
+        .unwrap();
+
+        let policy = settings.spelling.policy(settings.key);
+        let reference = match settings.key {
+            Some(key) => new_range.rand_in_key(&key, policy),
+            None => new_range.rand(policy),
+        }
+        .to_simple()
+        .spell_with(policy, |reference| {
+            vec![reference.shift(interval, direction)]
+        });
+        Exercise {
             reference,
+            mystery_note: reference.shift(interval, direction),
+            interval,
+            direction,
+            presentation,
+            cadence: None,
+            chord: None,
+            melody: None,
+        }
+    }
+
+    pub fn drill(&self) -> Drill {
+        match (self.cadence, self.chord, &self.melody) {
+            (Some(_), _, _) => Drill::ScaleDegrees,
+            (None, Some(_), _) => Drill::Chords,
+            (None, None, Some(_)) => Drill::Melodies,
+            (None, None, None) => Drill::Intervals,
+        }
+    }
+
+    /// A note of the key from the settings, or of a random major key, the reference being the
+    /// tonic right below it
+    fn draw_scale_degree(range: &NoteRange, settings: &Settings) -> Self {
+        let key = settings.key.unwrap_or_else(Key::rand_major);
+        let policy = settings.spelling.policy(Some(key));
+        let mystery_note = match settings.chromatic {
+            false => range.rand_in_key(&key, policy),
+            true => {
+                let note = range.rand(policy).to_simple();
+                // raised or lowered at random, for every chromatic syllable to come up
+                let spellings: Vec<Note> = note
+                    .enharmonics()
+                    .into_iter()
+                    .filter(|&spelled| ScaleDegree::of(spelled, &key).syllable().is_some())
+                    .collect();
+                match spellings.choose(&mut rand::thread_rng()) {
+                    Some(&spelled) if !key.contains(note) => spelled,
+                    _ => key.spell(note),
+                }
+            }
+        };
+
+        let tonic = key.tonic.semitones_from_c() as i8 + key.alteration;
+        let semitones = (mystery_note.to_simple().get_i8() - tonic).rem_euclid(12);
+        Exercise {
+            reference: key.spell(mystery_note.to_simple().shift(-semitones)),
             mystery_note,
-            note_length,
-            self.sample_rate,
-            playback_tx.clone(),
-        );
+            interval: Interval::from_semitones(semitones),
+            direction: Direction::Up,
+            presentation: Presentation::Melodic,
+            cadence: Some(key),
+            chord: None,
+            melody: None,
+        }
+    }
+
+    /// A chord of a quality from the settings, in any inversion, that fits in the range. The
+    /// mystery note is the chord tone to sing, or the root when the quality is picked
+    fn draw_chord(range: &NoteRange, settings: &Settings) -> Self {
+        let mut rng = rand::thread_rng();
+        let quality = *settings.chords.qualities().choose(&mut rng).unwrap();
+        let inversion = rng.gen_range(0..quality.n_notes());
+
+        let span = quality.span(inversion);
+
+        let policy = settings.spelling.policy(settings.key);
+        let root = range
+            .crop_top(span)
+            .expect("Settings::from_args checks that the chords fit in the range")
+            .rand(policy);
+        let chord = Chord::spelled(root.to_simple(), quality, inversion, policy).unwrap();
+        let root = chord.root;
+        let mystery_note = match settings.chord_answer {
+            ChordAnswerMode::Sing(tone) => chord.tone(tone).unwrap_or(root),
+            ChordAnswerMode::Keyboard => root,
+        };
 
-        (reference, mystery_note)
+        Exercise {
+            reference: chord.bass(),
+            mystery_note,
+            interval: Interval::from_semitones(
+                mystery_note.chromatic_distance_up_from(chord.bass()),
+            ),
+            direction: Direction::Up,
+            presentation: Presentation::Harmonic,
+            cadence: None,
+            chord: Some(chord),
+            melody: None,
+        }
     }
 
-    pub fn listen_for(&self, mystery_note: Note, pitch_detection_tx: Sender<bool>) {
-        let detection_duration = Duration::from_millis(1500);
-        audio::listen::listen_for_note_in_thread(
-            mystery_note.to_simple(),
-            detection_duration,
-            self.sample_rate,
-            pitch_detection_tx.clone(),
-        );
+    /// A tune in the key from the settings, or in a random major key
+    fn draw_melody(range: &NoteRange, settings: &Settings) -> Self {
+        let key = settings.key.unwrap_or_else(Key::rand_major);
+        let melody = Melody::rand(
+            range,
+            key,
+            settings.melody.length,
+            settings.melody.leaps.max_steps(),
+        )
+        .expect("Settings::from_args checks that the range holds a melody");
+
+        let (first, last) = (melody.notes[0], *melody.notes.last().unwrap());
+        let semitones = last.to_simple().get_i8() - first.to_simple().get_i8();
+        Exercise {
+            reference: first,
+            mystery_note: last,
+            interval: Interval::between(first, last)
+                .unwrap_or_else(|| Interval::from_semitones(semitones.abs())),
+            direction: match semitones < 0 {
+                true => Direction::Down,
+                false => Direction::Up,
+            },
+            presentation: Presentation::Melodic,
+            cadence: None,
+            chord: None,
+            melody: Some(melody),
+        }
     }
+}
 
-    pub fn ding(&self) {
-        audio::play_sample::play_ding_in_thread();
+/// Where the right note was heard from where it should sound, `None` in the right octave
+fn describe_octaves(outcome: &ListenOutcome) -> Option<String> {
+    match outcome.octaves_off()? {
+        0 => None,
+        -1 => Some(String::from("an octave below")),
+        1 => Some(String::from("an octave above")),
+        n if n < 0 => Some(format!("{} octaves below", -n)),
+        n => Some(format!("{n} octaves above")),
     }
+}
 
-    pub fn bad_ding(&self) {
-        audio::play_sample::play_wrong_sound_effect_in_thread();
+/// "an augmented fourth", "a major third"
+fn with_article(name: &str) -> String {
+    match name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        true => format!("an {name}"),
+        false => format!("a {name}"),
     }
+}
 
-    fn choose_notes(&self) -> (Note, Note) {
-        let interval = Interval::get_random_diatonic();
-        let direction = Direction::Up;
+/// Only the first letter, for the start of a sentence
+fn capitalized(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
 
-        let new_range = match direction {
-            Direction::Up => self.range.crop_top(interval.size_i8()),
-            Direction::Down => self.range.crop_bottom(interval.size_i8()),
+fn describe_cents(cent_deviation: CentDeviation) -> String {
+    match cent_deviation {
+        0 => String::from("in tune"),
+        c if c < 0 => format!("{} cents flat", -c),
+        c => format!("{c} cents sharp"),
+    }
+}
+
+impl fmt::Display for DegreeAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.expected, self.key)
+    }
+}
+
+impl Answer {
+    /// Whether the right note was missed for being in an octave that didn't count
+    fn wrong_octave(&self) -> Option<String> {
+        let heard = self.outcome.heard?;
+        match self
+            .outcome
+            .matching
+            .matches(heard.note, self.outcome.target)
+        {
+            true => None,
+            false => describe_octaves(&self.outcome),
+        }
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(MelodyAnswer { melody, heard }) = &self.melody {
+            let n_notes = melody.notes.len();
+            let n_right = heard.iter().filter(|outcome| outcome.success).count();
+            let first_miss = melody.notes.iter().zip(heard).find(|(_, o)| !o.success);
+            let n_displaced = heard
+                .iter()
+                .filter(|o| describe_octaves(o).is_some())
+                .count();
+            return match first_miss {
+                None if n_displaced > 0 => write!(
+                    f,
+                    "Well done! All {n_notes} notes, {n_displaced} of them in another octave"
+                ),
+                None => write!(f, "Well done! All {n_notes} notes"),
+                Some((note, outcome)) => {
+                    let sung = match outcome.heard {
+                        Some(heard) => format!("sang {}", melody.key.spell(heard.note)),
+                        None => String::from("nothing heard"),
+                    };
+                    write!(f, "{n_right} of {n_notes} notes, {sung} instead of {note}")
+                }
+            };
+        }
+        let octaves = match describe_octaves(&self.outcome) {
+            Some(octaves) => format!(", {octaves}"),
+            None => String::new(),
         };
 
-        let reference = new_range.rand();
-        (reference, reference.up(interval))
+        if let Some(ChordAnswer { chord, tone, picked }) = self.chord {
+            let Some(tone) = tone else {
+                return match picked {
+                    Some(picked) if picked == chord.quality => write!(f, "Well done! {chord}"),
+                    Some(picked) => write!(f, "You picked {picked}, it was {chord}"),
+                    None => write!(f, "It was {chord}"),
+                };
+            };
+            let expected = chord.tone(tone).unwrap_or(chord.root).spelling();
+            return match self.outcome.heard {
+                None => write!(f, "Nothing heard, the {tone} of {chord} is {expected}"),
+                Some(heard) if self.outcome.success => write!(
+                    f,
+                    "Well done! {expected} is the {tone} of {chord}{octaves}, {}",
+                    describe_cents(heard.cent_deviation)
+                ),
+                Some(heard) => write!(
+                    f,
+                    "You sang {}, the {tone} of {chord} is {expected}",
+                    heard.note
+                ),
+            };
+        }
+
+        if let Some(degree) = self.degree {
+            let Some(heard) = self.outcome.heard else {
+                return write!(f, "Nothing heard, it was {degree}");
+            };
+            let cents = describe_cents(heard.cent_deviation);
+            return match (self.outcome.success, degree.sung) {
+                (true, _) => write!(f, "Well done! {degree}{octaves}, {cents}"),
+                (false, Some(sung)) if sung == degree.expected => {
+                    let off = self.wrong_octave().unwrap_or(cents);
+                    write!(f, "Right degree, {}, but {off}", degree.expected)
+                }
+                (false, Some(sung)) => write!(f, "You sang {sung}, it was {degree}"),
+                (false, None) => write!(f, "It was {degree}"),
+            };
+        }
+
+        let expected = self.interval.to_string().to_lowercase();
+        let heard = match self.outcome.heard {
+            Some(heard) => heard,
+            None => {
+                return write!(
+                    f,
+                    "Nothing heard, expected {} {}",
+                    with_article(&expected),
+                    self.direction
+                )
+            }
+        };
+        let cents = describe_cents(heard.cent_deviation);
+
+        match (self.outcome.success, self.sung_interval) {
+            (true, _) => write!(
+                f,
+                "Well done! {} {}{octaves}, {cents}, in {} ms",
+                capitalized(&expected),
+                self.direction,
+                self.outcome.elapsed.as_millis()
+            ),
+            (false, Some(sung)) if sung.size_i8() == self.interval.size_i8() % 12 => {
+                let off = self.wrong_octave().unwrap_or(cents);
+                write!(f, "Right {expected} but {off}")
+            }
+            (false, Some(sung)) => write!(
+                f,
+                "You sang {}, expected {} {}",
+                with_article(&sung.to_string().to_lowercase()),
+                with_article(&expected),
+                self.direction
+            ),
+            (false, None) => write!(f, "Expected {} {}", with_article(&expected), self.direction),
+        }
     }
 }
 
@@ -66,8 +1308,477 @@ impl IntervalTrainer {
 pub enum Scene {
     #[default]
     Idle,
-    PlayingSound(Note, Note),
-    Listening1(Note, Note),
-    Listening2(Note, Note),
-    Concluding(Note, Note),
+    PlayingSound,
+    /// for the note of the exercise to sing back at that index, the first one of an interval
+    /// being the reference
+    Listening(usize),
+    /// picking the quality of a chord
+    Choosing,
+    Concluding,
+    Statistics,
+    /// showing how far off whatever is heard is, outside of any exercise
+    Tuner,
+    /// measuring the room and the user's instrument to suggest detection thresholds
+    Calibrating,
+    /// picking the input and output devices
+    Devices,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::detection::{HeardNote, OctaveMatching};
+    use crate::music::{interval::BaseInterval, interval::Quality, SimpleNote};
+    use std::time::Instant;
+
+    fn headless(audio: AudioBackendKind) -> IntervalTrainer {
+        let settings = Settings {
+            audio,
+            history: HistoryLocation::Disabled,
+            direction: crate::settings::DirectionMode::Mixed,
+            presentation: crate::settings::PresentationMode::Mixed,
+            ..Default::default()
+        };
+        IntervalTrainer::init(NoteRange::treble_staff(), settings)
+    }
+
+    fn run_exercise(trainer: &mut IntervalTrainer) -> Answer {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        trainer.start();
+        while !matches!(trainer.scene, Scene::Concluding) {
+            assert!(Instant::now() < deadline, "stuck in {:?}", trainer.scene);
+            trainer.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        trainer.scene = Scene::Idle;
+
+        trainer.answer().unwrap().clone()
+    }
+
+    #[test]
+    fn headless_loopback_exercises_are_matched() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+
+        for _ in 0..10 {
+            let answer = run_exercise(&mut trainer);
+            assert!(answer.outcome.success, "{answer}");
+            assert_eq!(
+                answer.sung_interval.unwrap().size_i8(),
+                answer.interval.size_i8()
+            );
+        }
+        let attempts: u32 = trainer
+            .statistics()
+            .intervals
+            .iter()
+            .map(|i| i.attempts)
+            .sum();
+        assert_eq!(attempts, 10);
+    }
+
+    /// Sings the reference back right and the mystery note a semitone sharp
+    struct SharpSinger(LoopbackBackend);
+
+    impl AudioBackend for SharpSinger {
+        fn play_notes(
+            &self,
+            n1: Note,
+            n2: Note,
+            presentation: Presentation,
+            note_length: Duration,
+        ) -> Result<(), String> {
+            let minor_second = Interval::from_semitones(1);
+            self.0
+                .play_notes(n1, n2.up(minor_second), presentation, note_length)
+        }
+
+        fn play_chord(&self, notes: &[Note], note_length: Duration) -> Result<(), String> {
+            self.0.play_chord(notes, note_length)
+        }
+
+        fn play_cadence(&self, chords: &[Vec<Note>], chord_length: Duration) -> Result<(), String> {
+            self.0.play_cadence(chords, chord_length)
+        }
+
+        fn play_note(&self, note: Note, note_length: Duration) -> Result<(), String> {
+            self.0.play_note(note, note_length)
+        }
+
+        fn play_effect(&self, effect: SoundEffect) {
+            self.0.play_effect(effect)
+        }
+
+        fn listen_for_note(
+            &self,
+            target_note: SimpleNote,
+            matching: OctaveMatching,
+            settings: DetectionSettings,
+            readings: Sender<PitchReading>,
+        ) -> Result<ListenOutcome, String> {
+            self.0
+                .listen_for_note(target_note, matching, settings, readings)
+        }
+
+        fn input_device(&self) -> Option<String> {
+            None
+        }
+
+        fn track_pitch(&self, _: DetectionSettings, _: Sender<PitchReading>) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn exercises_need_at_most_double_accidentals() {
+        use crate::music::IntervalPool;
+        use crate::settings::SpellingMode;
+
+        let scheduler = Scheduler::new();
+        let keys = [
+            None,
+            Some(Key::parse("C#").unwrap()),
+            Some(Key::parse("Cb").unwrap()),
+        ];
+        let spellings = [
+            SpellingMode::Auto,
+            SpellingMode::Sharps,
+            SpellingMode::Flats,
+            SpellingMode::Random,
+        ];
+        for key in keys {
+            for spelling in spellings {
+                let settings = Settings {
+                    intervals: IntervalPool::all(),
+                    direction: crate::settings::DirectionMode::Mixed,
+                    key,
+                    spelling,
+                    ..Default::default()
+                };
+                for _ in 0..200 {
+                    let exercise = Exercise::draw(&NoteRange::piano(), &settings, &scheduler);
+                    for note in [exercise.reference, exercise.mystery_note] {
+                        assert!(note.alteration.abs() <= 2, "{note} in {key:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn headless_misses_show_up_in_the_confusion_grid() {
+        // melodic and ascending, unlike `headless`, for the sharp singer to be heard
+        let settings = Settings {
+            audio: AudioBackendKind::Null,
+            history: HistoryLocation::Disabled,
+            ..Default::default()
+        };
+        let mut trainer = IntervalTrainer::init(NoteRange::treble_staff(), settings);
+        trainer.backend = Arc::new(SharpSinger(LoopbackBackend::new(
+            44_100,
+            Default::default(),
+        )));
+
+        let mut asked = Vec::new();
+        for _ in 0..5 {
+            let answer = run_exercise(&mut trainer);
+            assert!(!answer.outcome.success, "{answer}");
+            asked.push(crate::stats::interval_class(answer.interval));
+        }
+
+        let confusion = trainer.statistics().confusion;
+        for class in asked {
+            let sung = (class + 1) % crate::stats::N_INTERVAL_CLASSES;
+            assert!(confusion[class][sung] > 0, "{confusion:?}");
+        }
+        let total: u32 = confusion.iter().flatten().sum();
+        assert_eq!(total, 5);
+    }
+
+    #[test]
+    fn headless_scale_degrees_are_named() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+        trainer.settings.drill = Drill::ScaleDegrees;
+
+        for _ in 0..5 {
+            let answer = run_exercise(&mut trainer);
+            let degree = answer.degree.unwrap();
+            assert!(answer.outcome.success, "{answer}");
+            assert_eq!(degree.sung, Some(degree.expected));
+            assert_eq!(degree.expected.alteration, 0);
+        }
+        // interval statistics are left alone
+        assert!(trainer.statistics().is_empty());
+    }
+
+    #[test]
+    fn chromatic_scale_degrees_are_raised_or_lowered() {
+        let c_major = Key::parse("C").unwrap();
+        let settings = Settings {
+            drill: Drill::ScaleDegrees,
+            key: Some(c_major),
+            chromatic: true,
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new();
+        let mut syllables = Vec::new();
+        for _ in 0..500 {
+            let exercise = Exercise::draw(&NoteRange::treble_staff(), &settings, &scheduler);
+            let degree = ScaleDegree::of(exercise.mystery_note, &c_major);
+            syllables.push(degree.syllable().unwrap());
+        }
+        for syllable in ["di", "ri", "fi", "si", "li", "ra", "me", "se", "le", "te"] {
+            assert!(syllables.contains(&syllable), "{syllable}");
+        }
+
+        // sung back right, even when lowered
+        let mut trainer = headless(AudioBackendKind::Loopback);
+        trainer.settings.drill = Drill::ScaleDegrees;
+        trainer.settings.key = Some(c_major);
+        trainer.settings.chromatic = true;
+        for _ in 0..5 {
+            let answer = run_exercise(&mut trainer);
+            let degree = answer.degree.unwrap();
+            assert!(answer.outcome.success, "{answer}");
+            assert_eq!(degree.sung, Some(degree.expected));
+        }
+    }
+
+    #[test]
+    fn headless_chord_qualities_are_picked() {
+        let mut trainer = headless(AudioBackendKind::Null);
+        trainer.settings.drill = Drill::Chords;
+        trainer.settings.chords = crate::settings::ChordSet::All;
+
+        for pick_the_right_one in [true, false] {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            trainer.start();
+            while !matches!(trainer.scene, Scene::Choosing) {
+                assert!(Instant::now() < deadline, "stuck in {:?}", trainer.scene);
+                trainer.update();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let chord = trainer.chord().unwrap();
+            let qualities = trainer.chord_qualities();
+            let right = qualities.iter().position(|&q| q == chord.quality).unwrap();
+            let index = match pick_the_right_one {
+                true => right,
+                false => (right + 1) % qualities.len(),
+            };
+            trainer.pick_quality(index);
+            trainer.scene = Scene::Idle;
+
+            let answer = trainer.answer().unwrap();
+            assert_eq!(answer.outcome.success, pick_the_right_one, "{answer}");
+            assert_eq!(answer.chord.unwrap().picked, Some(qualities[index]));
+        }
+        // interval statistics are left alone
+        assert!(trainer.statistics().is_empty());
+    }
+
+    #[test]
+    fn headless_chord_tones_are_sung() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+        trainer.settings.drill = Drill::Chords;
+        trainer.settings.chord_answer = ChordAnswerMode::Sing(ChordTone::Root);
+
+        for _ in 0..5 {
+            let answer = run_exercise(&mut trainer);
+            let chord = answer.chord.unwrap();
+            assert_eq!(chord.tone, Some(ChordTone::Root));
+            assert_eq!(chord.picked, None);
+            assert_eq!(
+                answer.outcome.target,
+                chord.chord.tone(ChordTone::Root).unwrap().to_simple()
+            );
+            // the loopback plays the whole chord back, too many notes to name any of them
+            let [(_, readings)] = trainer.pitch_trace()[..] else {
+                panic!("one note to sing");
+            };
+            assert!(readings.iter().any(|reading| reading.power > 1.0));
+
+            let record = trainer.records.last().unwrap();
+            assert_eq!(record.drill, Drill::Chords);
+            assert_eq!(record.target, chord.chord.tone(ChordTone::Root).unwrap());
+            assert_eq!(record.quality, Some(chord.chord.quality));
+            assert_eq!(record.picked, None);
+        }
+    }
+
+    #[test]
+    fn headless_melodies_are_sung_back() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+        trainer.settings.drill = Drill::Melodies;
+        trainer.settings.melody.length = 5;
+        trainer.settings.melody.leaps = crate::settings::Leaps::Octaves;
+
+        let answer = run_exercise(&mut trainer);
+        let melody = answer.melody.unwrap();
+        assert_eq!(melody.heard.len(), 5);
+        assert!(melody.heard.iter().all(|outcome| outcome.success));
+        // one record per step, none of them for the interval statistics
+        assert_eq!(trainer.records.len(), 4);
+        assert!(trainer.statistics().is_empty());
+        // octave leaps included
+        for record in &trainer.records {
+            let semitones = record.target.chromatic_distance_up_from(record.reference);
+            assert_eq!(record.interval.size_i8(), semitones.abs());
+        }
+    }
+
+    #[test]
+    fn headless_transposing_instruments_are_heard_as_written() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+        trainer.settings.instrument =
+            Some(crate::instrument::Instrument::parse("trumpet").unwrap());
+        trainer.settings.octaves = OctaveMatching::Strict;
+
+        for _ in 0..5 {
+            let answer = run_exercise(&mut trainer);
+            assert!(answer.outcome.success, "{answer}");
+            assert_eq!(answer.outcome.heard.unwrap().note, answer.outcome.target);
+        }
+    }
+
+    #[test]
+    fn headless_pitch_is_traced_while_listening() {
+        let mut trainer = headless(AudioBackendKind::Loopback);
+
+        let answer = run_exercise(&mut trainer);
+        let trace = trainer.pitch_trace();
+        assert_eq!(trace.len(), 2);
+        for (expected, readings) in trace {
+            let last = readings.last().unwrap().heard.unwrap();
+            assert!(last.cents_off(expected).abs() < 20, "{answer}");
+        }
+
+        trainer.toggle_tuner();
+        assert!(matches!(trainer.scene, Scene::Tuner));
+        trainer.update();
+        assert_eq!(trainer.tuning().map(|(_, heard)| heard), None);
+        trainer.toggle_tuner();
+        assert!(matches!(trainer.scene, Scene::Idle));
+    }
+
+    #[test]
+    fn headless_null_exercises_are_missed() {
+        let mut trainer = headless(AudioBackendKind::Null);
+
+        let answer = run_exercise(&mut trainer);
+        assert!(!answer.outcome.success);
+        assert_eq!(answer.outcome.heard, None);
+    }
+
+    #[test]
+    fn headless_calibration_needs_an_input_device() {
+        for audio in [AudioBackendKind::Null, AudioBackendKind::Loopback] {
+            let mut trainer = headless(audio);
+            trainer.toggle_calibration();
+            assert!(matches!(trainer.scene, Scene::Idle));
+            assert_eq!(trainer.notice(), Some("no input device to calibrate"));
+        }
+    }
+
+    #[test]
+    fn headless_picked_devices_are_kept() {
+        let dir = std::env::temp_dir().join(format!("devices_trainer_{}", std::process::id()));
+        let settings = Settings {
+            audio: AudioBackendKind::Null,
+            history: HistoryLocation::At(dir.join("history.jsonl")),
+            ..Default::default()
+        };
+        let usb = DeviceChoice { host: String::from("ALSA"), name: String::from("USB Mic") };
+
+        let mut trainer = IntervalTrainer::init(NoteRange::treble_staff(), settings.clone());
+        trainer.toggle_devices();
+        assert!(matches!(trainer.scene, Scene::Devices));
+        trainer.device_menu.as_mut().unwrap().inputs = vec![usb.clone()];
+        trainer.pick_device(1);
+        assert_eq!(trainer.devices().input, Some(usb.clone()));
+        trainer.toggle_devices();
+        assert!(matches!(trainer.scene, Scene::Idle));
+
+        let mut trainer = IntervalTrainer::init(NoteRange::treble_staff(), settings.clone());
+        assert_eq!(trainer.devices().input, Some(usb.clone()));
+        assert_eq!(trainer.devices().output, None);
+        trainer.toggle_devices();
+        trainer.pick_device(0);
+        assert_eq!(trainer.devices().input, None);
+
+        // devices from the command line are used but not kept
+        let speakers = DeviceChoice { host: String::from("ALSA"), name: String::from("Speakers") };
+        let devices = DeviceChoices { input: None, output: Some(speakers.clone()) };
+        let mut trainer = IntervalTrainer::init(
+            NoteRange::treble_staff(),
+            Settings { devices, ..settings.clone() },
+        );
+        assert_eq!(trainer.devices().output, Some(speakers));
+        trainer.toggle_devices();
+        trainer.device_menu.as_mut().unwrap().inputs = vec![usb.clone()];
+        trainer.pick_device(1);
+        let trainer = IntervalTrainer::init(NoteRange::treble_staff(), settings);
+        assert_eq!(trainer.devices().input, Some(usb));
+        assert_eq!(trainer.devices().output, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn answer(success: bool, heard: Option<(i8, i8)>, sung_interval: Option<Interval>) -> Answer {
+        Answer {
+            interval: Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect },
+            direction: Direction::Up,
+            outcome: ListenOutcome {
+                target: SimpleNote::new(76),
+                heard: heard.map(|(note, cent_deviation)| HeardNote {
+                    note: SimpleNote::new(note),
+                    frequency: 0.0,
+                    cent_deviation,
+                }),
+                success,
+                elapsed: Duration::from_millis(640),
+                matching: OctaveMatching::Tolerant,
+            },
+            sung_interval,
+            degree: None,
+            chord: None,
+            melody: None,
+        }
+    }
+
+    #[test]
+    fn answer_feedback() {
+        let minor_sixth = Interval { base_interval: BaseInterval::Sixth, quality: Quality::Minor };
+        let fifth = Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect };
+
+        assert_eq!(
+            answer(false, Some((77, 5)), Some(minor_sixth)).to_string(),
+            "You sang a minor sixth, expected a perfect fifth up"
+        );
+        assert_eq!(
+            answer(false, Some((76, -30)), Some(fifth)).to_string(),
+            "Right perfect fifth but 30 cents flat"
+        );
+        assert_eq!(
+            answer(true, Some((76, 4)), Some(fifth)).to_string(),
+            "Well done! Perfect fifth up, 4 cents sharp, in 640 ms"
+        );
+        assert_eq!(
+            answer(true, Some((64, 4)), Some(fifth)).to_string(),
+            "Well done! Perfect fifth up, an octave below, 4 cents sharp, in 640 ms"
+        );
+        let mut strict = answer(false, Some((64, 4)), Some(fifth));
+        strict.outcome.matching = OctaveMatching::Strict;
+        assert_eq!(
+            strict.to_string(),
+            "Right perfect fifth but an octave below"
+        );
+        assert_eq!(
+            answer(false, None, None).to_string(),
+            "Nothing heard, expected a perfect fifth up"
+        );
+        let tritone = Interval { base_interval: BaseInterval::Fourth, quality: Quality::Augmented };
+        assert_eq!(
+            answer(false, Some((75, 0)), Some(tritone)).to_string(),
+            "You sang an augmented fourth, expected a perfect fifth up"
+        );
+    }
 }
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use crate::music::{Direction, Interval, IntervalPool, Presentation};
+
+/// Cards climb one box per fast right answer and fall back to the first box on a miss
+const N_BOXES: u8 = 5;
+
+/// A right answer slower than this doesn't promote the card
+const SLOW_RESPONSE: Duration = Duration::from_millis(1000);
+
+/// Leitner style spaced repetition over (interval, direction, presentation) cards, a harmonic
+/// major third being a different skill from a melodic one
+///
+/// Each box is drawn half as often as the one before it, so intervals that keep getting missed
+/// come back a lot more often than the ones that are already known
+#[derive(Clone, Debug, Default)]
+pub struct Scheduler {
+    cards: Vec<Card>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Card {
+    pub interval: Interval,
+    pub direction: Direction,
+    pub presentation: Presentation,
+    pub box_index: u8,
+    pub attempts: u32,
+    pub successes: u32,
+    pub total_response_time: Duration,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler::default()
+    }
+
+    pub fn record(
+        &mut self,
+        interval: Interval,
+        direction: Direction,
+        presentation: Presentation,
+        success: bool,
+        response_time: Duration,
+    ) {
+        let card = match self
+            .cards
+            .iter()
+            .position(|c| c.is(interval, direction, presentation))
+        {
+            Some(index) => &mut self.cards[index],
+            None => {
+                self.cards
+                    .push(Card::new(interval, direction, presentation));
+                self.cards.last_mut().unwrap()
+            }
+        };
+
+        card.attempts += 1;
+        card.total_response_time += response_time;
+        if success {
+            card.successes += 1;
+            if response_time <= SLOW_RESPONSE {
+                card.box_index = (card.box_index + 1).min(N_BOXES - 1);
+            }
+        } else {
+            card.box_index = 0;
+        }
+    }
+
+    pub fn card(
+        &self,
+        interval: Interval,
+        direction: Direction,
+        presentation: Presentation,
+    ) -> Option<&Card> {
+        self.cards
+            .iter()
+            .find(|c| c.is(interval, direction, presentation))
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    /// Relative likelihood of drawing this card, cards never seen before are in the first box
+    pub fn weight(
+        &self,
+        interval: Interval,
+        direction: Direction,
+        presentation: Presentation,
+    ) -> f64 {
+        let box_index = self
+            .card(interval, direction, presentation)
+            .map_or(0, |c| c.box_index);
+
+        2.0_f64.powi((N_BOXES - 1 - box_index).into())
+    }
+
+    /// Picks from the pool, favouring the intervals in the lowest boxes
+    pub fn choose(
+        &self,
+        pool: &IntervalPool,
+        direction: Direction,
+        presentation: Presentation,
+        filter: impl Fn(&Interval) -> bool,
+    ) -> Option<Interval> {
+        pool.choose_scaled(|interval| match filter(interval) {
+            true => self.weight(*interval, direction, presentation),
+            false => 0.0,
+        })
+    }
+}
+
+impl Card {
+    fn new(interval: Interval, direction: Direction, presentation: Presentation) -> Self {
+        Card {
+            interval,
+            direction,
+            presentation,
+            box_index: 0,
+            attempts: 0,
+            successes: 0,
+            total_response_time: Duration::ZERO,
+        }
+    }
+
+    fn is(&self, interval: Interval, direction: Direction, presentation: Presentation) -> bool {
+        self.interval == interval
+            && self.direction == direction
+            && self.presentation == presentation
+    }
+
+    pub fn average_response_time(&self) -> Option<Duration> {
+        match self.attempts {
+            0 => None,
+            n => Some(self.total_response_time / n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::interval::{BaseInterval, Quality};
+
+    const FIFTH: Interval =
+        Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect };
+    const FAST: Duration = Duration::from_millis(500);
+    const MELODIC: Presentation = Presentation::Melodic;
+
+    #[test]
+    fn misses_send_cards_back_to_the_first_box() {
+        let mut scheduler = Scheduler::new();
+        let unseen_weight = scheduler.weight(FIFTH, Direction::Up, MELODIC);
+
+        scheduler.record(FIFTH, Direction::Up, MELODIC, true, FAST);
+        scheduler.record(FIFTH, Direction::Up, MELODIC, true, FAST);
+        assert_eq!(
+            scheduler.weight(FIFTH, Direction::Up, MELODIC),
+            unseen_weight / 4.0
+        );
+        assert_eq!(
+            scheduler.weight(FIFTH, Direction::Down, MELODIC),
+            unseen_weight
+        );
+        // a harmonic fifth is a card of its own
+        let harmonic = scheduler.weight(FIFTH, Direction::Up, Presentation::Harmonic);
+        assert_eq!(harmonic, unseen_weight);
+
+        scheduler.record(
+            FIFTH,
+            Direction::Up,
+            MELODIC,
+            true,
+            Duration::from_millis(1400),
+        );
+        assert_eq!(
+            scheduler.weight(FIFTH, Direction::Up, MELODIC),
+            unseen_weight / 4.0
+        );
+
+        scheduler.record(FIFTH, Direction::Up, MELODIC, false, FAST);
+        assert_eq!(
+            scheduler.weight(FIFTH, Direction::Up, MELODIC),
+            unseen_weight
+        );
+
+        let card = scheduler.card(FIFTH, Direction::Up, MELODIC).unwrap();
+        assert_eq!((card.attempts, card.successes), (4, 3));
+    }
+
+    #[test]
+    fn boxes_are_capped() {
+        let mut scheduler = Scheduler::new();
+        for _ in 0..20 {
+            scheduler.record(FIFTH, Direction::Down, MELODIC, true, FAST);
+        }
+
+        assert_eq!(
+            scheduler
+                .card(FIFTH, Direction::Down, MELODIC)
+                .unwrap()
+                .box_index,
+            N_BOXES - 1
+        );
+        assert_eq!(scheduler.weight(FIFTH, Direction::Down, MELODIC), 1.0);
+    }
+}
@@ -3,33 +3,56 @@
 //! # A cool ear trainer
 
 mod audio;
+mod calibration;
+mod drills;
+mod history;
+mod instrument;
 mod interval_trainer;
 mod music;
 mod render;
+mod settings;
+mod stats;
 
-use std::{
-    sync::mpsc,
-    sync::mpsc::{Receiver, Sender},
-};
-
-use sdl2::{event::Event, image::InitFlag, keyboard::Keycode};
+use sdl2::{event::Event, image::InitFlag, keyboard::Keycode, pixels::Color};
 
 use crate::{
     interval_trainer::{IntervalTrainer, Scene},
-    music::NoteRange,
     render::Sprites,
+    settings::{Command, Settings},
 };
 
 const WINDOW_WIDTH: u32 = 1000;
-const WINDOW_HEIGHT: u32 = 400;
+
+const BLACK: Color = Color::RGB(0, 0, 0);
+const RED: Color = Color::RGB(210, 60, 50);
 
 fn main() -> Result<(), String> {
+    let settings = Settings::from_args(std::env::args())?;
+    if let Command::Render(options) = &settings.command {
+        let exercises = drills::render_drills(&settings.range, &settings, options)?;
+        println!(
+            "{} exercises and their answer key written to {}",
+            exercises.len(),
+            options.out.display()
+        );
+        return Ok(());
+    }
+    if let Command::Devices = settings.command {
+        print!("{}", audio::devices::describe());
+        return Ok(());
+    }
+
+    let mut trainer = IntervalTrainer::init(settings.range, settings);
+    // as tall as the staff, the grand staff needs more room
+    let window_height = trainer.staff().height();
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let _image_context = sdl2::image::init(InitFlag::PNG)?;
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
     let window = video_subsystem
-        .window("Interval Trainer", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .window("Interval Trainer", WINDOW_WIDTH, window_height)
         .position_centered()
         .build()
         .map_err(|e| e.to_string())?;
@@ -42,11 +65,9 @@ fn main() -> Result<(), String> {
 
     let texture_creator = canvas.texture_creator();
     let sprites = Sprites::init(&texture_creator)?;
-
-    let (playback_tx, playback_rx): (Sender<()>, Receiver<()>) = mpsc::channel();
-    let (pitch_detection_tx, pitch_detection_rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
-
-    let mut trainer = IntervalTrainer::init(NoteRange::treble_staff());
+    let font = render::load_font(&ttf_context, 16)?;
+    let large_font = render::load_font(&ttf_context, 96)?;
+    let mut statistics_texts = render::StatisticsTexts::default();
 
     // let cool_note = Note::parse_from_string("F3")?;
 
@@ -56,64 +77,121 @@ fn main() -> Result<(), String> {
                 Event::Quit { .. }
                 | Event::KeyDown { keycode: Option::Some(Keycode::Escape), .. } => break 'mainloop,
                 Event::KeyDown { keycode: Option::Some(Keycode::Space), .. } => {
-                    if let Scene::Concluding(_, _) = trainer.scene {
+                    if let Scene::Concluding | Scene::Statistics = trainer.scene {
                         trainer.scene = Scene::Idle;
                     }
-                    if let Scene::Idle = trainer.scene {
-                        let (reference, mystery_note) = trainer.start_playback(playback_tx.clone());
-                        trainer.scene = Scene::PlayingSound(reference, mystery_note);
+                    trainer.start();
+                }
+                Event::KeyDown { keycode: Option::Some(keycode), .. }
+                    if number_key(keycode).is_some() =>
+                {
+                    let index = number_key(keycode).unwrap() - 1;
+                    match trainer.scene {
+                        Scene::Devices => trainer.pick_device(index),
+                        _ => trainer.pick_quality(index),
                     }
                 }
+                Event::KeyDown { keycode: Option::Some(Keycode::T), .. } => {
+                    trainer.toggle_tuner();
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::C), .. } => {
+                    trainer.toggle_calibration();
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::D), .. } => {
+                    trainer.toggle_devices();
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::Tab), .. } => {
+                    trainer.switch_device_list();
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::Return), .. } => {
+                    trainer.accept_calibration();
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::P), .. } => {
+                    if let Scene::Statistics = trainer.scene {
+                        trainer.cycle_statistics_presentation();
+                    }
+                }
+                Event::KeyDown { keycode: Option::Some(Keycode::S), .. } => {
+                    trainer.scene = match trainer.scene {
+                        Scene::Idle | Scene::Concluding => Scene::Statistics,
+                        Scene::Statistics => Scene::Idle,
+                        scene => scene,
+                    };
+                }
                 _ => {}
             }
         }
 
-        if let Scene::Idle = trainer.scene {
-            render::render_staff(None, None, &sprites, &mut canvas)?;
-        }
-
-        if let Scene::PlayingSound(reference, mystery_note) = trainer.scene {
-            render::render_staff(Some(reference), None, &sprites, &mut canvas)?;
-            if let Ok(()) = playback_rx.try_recv() {
-                trainer.listen_for(reference, pitch_detection_tx.clone());
-                trainer.scene = Scene::Listening1(reference, mystery_note);
+        trainer.update();
+
+        match (trainer.scene, trainer.chord()) {
+            (Scene::Statistics, _) => render::render_statistics(
+                trainer.statistics(),
+                &mut statistics_texts,
+                &font,
+                &texture_creator,
+                &mut canvas,
+            )?,
+            (Scene::Devices, _) => {
+                if let Some(menu) = trainer.device_menu() {
+                    render::render_devices(
+                        menu,
+                        trainer.devices().input.as_ref(),
+                        trainer.devices().output.as_ref(),
+                        &font,
+                        &mut canvas,
+                    )?;
+                }
             }
+            (Scene::Tuner, _) => render::render_tuner(
+                trainer.tuning(),
+                trainer.detection().cent_deviation_threshold,
+                &large_font,
+                &font,
+                &mut canvas,
+            )?,
+            (Scene::Calibrating, _) => render::render_input_level(
+                trainer.input_level(),
+                trainer.detection().power_threshold,
+                &font,
+                &mut canvas,
+            )?,
+            (Scene::Concluding, Some(chord)) => render::render_chord(
+                &chord.notes(),
+                trainer.staff(),
+                trainer.key(),
+                &sprites,
+                &mut canvas,
+            )?,
+            _ => render::render_staff(
+                &trainer.shown_notes(),
+                trainer.staff(),
+                trainer.key(),
+                &sprites,
+                &mut canvas,
+            )?,
         }
 
-        if let Scene::Listening1(reference, mystery_note) = trainer.scene {
-            render::render_staff(Some(reference), None, &sprites, &mut canvas)?;
-            match pitch_detection_rx.try_recv() {
-                Ok(true) => {
-                    trainer.ding();
-                    trainer.listen_for(mystery_note, pitch_detection_tx.clone());
-                    trainer.scene = Scene::Listening2(reference, mystery_note);
-                }
-                Ok(false) => {
-                    trainer.bad_ding();
-                    trainer.listen_for(mystery_note, pitch_detection_tx.clone());
-                    trainer.scene = Scene::Listening2(reference, mystery_note);
-                }
-                Err(_) => {}
-            }
+        if let Scene::Concluding = trainer.scene {
+            render::render_pitch_trace(
+                &trainer.pitch_trace(),
+                trainer.detection().cent_deviation_threshold,
+                &mut canvas,
+            )?;
         }
 
-        if let Scene::Listening2(reference, mystery_note) = trainer.scene {
-            render::render_staff(Some(reference), None, &sprites, &mut canvas)?;
-            match pitch_detection_rx.try_recv() {
-                Ok(true) => {
-                    trainer.ding();
-                    trainer.scene = Scene::Concluding(reference, mystery_note);
-                }
-                Ok(false) => {
-                    trainer.bad_ding();
-                    trainer.scene = Scene::Concluding(reference, mystery_note);
-                }
-                Err(_) => {}
-            }
+        let text = match trainer.scene {
+            Scene::Concluding => trainer.answer().map(|answer| answer.to_string()),
+            _ => trainer.prompt(),
+        };
+        if let Some(text) = text {
+            let y = window_height as i32 - 30;
+            render::render_text(&text, 20, y, BLACK, &font, &mut canvas)?;
         }
 
-        if let Scene::Concluding(reference, mystery_note) = trainer.scene {
-            render::render_staff(Some(reference), Some(mystery_note), &sprites, &mut canvas)?;
+        if let Some(notice) = trainer.notice() {
+            let y = window_height as i32 - 60;
+            render::render_text(notice, 20, y, RED, &font, &mut canvas)?;
         }
 
         canvas.present();
@@ -122,6 +200,23 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// 1 to 9, from the top row
+fn number_key(keycode: Keycode) -> Option<usize> {
+    let number = match keycode {
+        Keycode::Num1 => 1,
+        Keycode::Num2 => 2,
+        Keycode::Num3 => 3,
+        Keycode::Num4 => 4,
+        Keycode::Num5 => 5,
+        Keycode::Num6 => 6,
+        Keycode::Num7 => 7,
+        Keycode::Num8 => 8,
+        Keycode::Num9 => 9,
+        _ => return None,
+    };
+    Some(number)
+}
+
 fn print_type_of<T>(_: &T) {
     println!("{}", std::any::type_name::<T>())
 }
@@ -129,7 +224,7 @@ fn print_type_of<T>(_: &T) {
 /*
 backend usage for reference:
 
-    const SAMPLE_RATE: u16 = 44_100;
+    const SAMPLE_RATE: u32 = 44_100;
 
     let range = NoteRange::tenor_voice();
     let (reference_note, mystery_note) = choose_notes(&range);
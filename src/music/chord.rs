@@ -0,0 +1,334 @@
+use std::fmt;
+
+use crate::music::interval::{BaseInterval, Interval, Quality};
+use crate::music::simple_note::NamingPolicy;
+use crate::music::{Note, NoteName, SimpleNote};
+
+const MAJOR_THIRD: Interval =
+    Interval { base_interval: BaseInterval::Third, quality: Quality::Major };
+const MINOR_THIRD: Interval =
+    Interval { base_interval: BaseInterval::Third, quality: Quality::Minor };
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    DominantSeventh,
+    MajorSeventh,
+    MinorSeventh,
+    HalfDiminishedSeventh,
+    DiminishedSeventh,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChordTone {
+    Root,
+    Third,
+    Fifth,
+    Seventh,
+}
+
+/// A chord in close position, its lowest notes moved up an octave for every inversion
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Chord {
+    pub root: Note,
+    pub quality: ChordQuality,
+    /// 0 for root position, up to 3 for a seventh chord with the seventh in the bass
+    pub inversion: u8,
+}
+
+impl ChordQuality {
+    pub fn triads() -> Vec<ChordQuality> {
+        vec![
+            ChordQuality::Major,
+            ChordQuality::Minor,
+            ChordQuality::Diminished,
+            ChordQuality::Augmented,
+        ]
+    }
+
+    pub fn sevenths() -> Vec<ChordQuality> {
+        vec![
+            ChordQuality::DominantSeventh,
+            ChordQuality::MajorSeventh,
+            ChordQuality::MinorSeventh,
+            ChordQuality::HalfDiminishedSeventh,
+            ChordQuality::DiminishedSeventh,
+        ]
+    }
+
+    pub fn all() -> Vec<ChordQuality> {
+        let mut qualities = ChordQuality::triads();
+        qualities.extend(ChordQuality::sevenths());
+        qualities
+    }
+
+    /// The thirds stacked from the root
+    pub fn thirds(&self) -> Vec<Interval> {
+        match self {
+            ChordQuality::Major => vec![MAJOR_THIRD, MINOR_THIRD],
+            ChordQuality::Minor => vec![MINOR_THIRD, MAJOR_THIRD],
+            ChordQuality::Diminished => vec![MINOR_THIRD, MINOR_THIRD],
+            ChordQuality::Augmented => vec![MAJOR_THIRD, MAJOR_THIRD],
+            ChordQuality::DominantSeventh => vec![MAJOR_THIRD, MINOR_THIRD, MINOR_THIRD],
+            ChordQuality::MajorSeventh => vec![MAJOR_THIRD, MINOR_THIRD, MAJOR_THIRD],
+            ChordQuality::MinorSeventh => vec![MINOR_THIRD, MAJOR_THIRD, MINOR_THIRD],
+            ChordQuality::HalfDiminishedSeventh => vec![MINOR_THIRD, MINOR_THIRD, MAJOR_THIRD],
+            ChordQuality::DiminishedSeventh => vec![MINOR_THIRD, MINOR_THIRD, MINOR_THIRD],
+        }
+    }
+
+    /// Semitones from the root to the top of the voicing in that inversion, whatever the root
+    pub fn span(&self, inversion: u8) -> i8 {
+        let c4 = Note { name: NoteName::C, alteration: 0, octave: 4 };
+        let notes = Chord { root: c4, quality: *self, inversion }.notes();
+        notes.last().unwrap().chromatic_distance_up_from(c4)
+    }
+
+    pub fn n_notes(&self) -> u8 {
+        self.thirds().len() as u8 + 1
+    }
+
+    /// As displayed, e.g. "half-diminished seventh"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        ChordQuality::all()
+            .into_iter()
+            .find(|quality| quality.to_string() == string)
+            .ok_or(format!("invalid chord quality: {string}"))
+    }
+}
+
+impl Chord {
+    pub fn new(root: Note, quality: ChordQuality, inversion: u8) -> Result<Self, &'static str> {
+        match inversion < quality.n_notes() {
+            true => Ok(Chord { root, quality, inversion }),
+            false => Err("not that many inversions"),
+        }
+    }
+
+    /// On a root spelled under the policy, unless a chord tone would then need a triple
+    /// accidental, e.g. a diminished seventh on B rather than on Cb in Gb major
+    pub fn spelled(
+        root: SimpleNote,
+        quality: ChordQuality,
+        inversion: u8,
+        policy: NamingPolicy,
+    ) -> Result<Self, &'static str> {
+        let root = root.spell_with(policy, |root| {
+            Chord { root, quality, inversion: 0 }.root_position()
+        });
+        Chord::new(root, quality, inversion)
+    }
+
+    /// Root, third, fifth and seventh if any, from the root up
+    pub fn root_position(&self) -> Vec<Note> {
+        let mut notes = vec![self.root];
+        for third in self.quality.thirds() {
+            notes.push(notes.last().unwrap().up(third));
+        }
+        notes
+    }
+
+    /// From the bass up
+    pub fn notes(&self) -> Vec<Note> {
+        let mut notes = self.root_position();
+        notes.rotate_left(self.inversion as usize);
+        let n_moved = notes.len() - self.inversion as usize;
+        for note in &mut notes[n_moved..] {
+            note.octave += 1;
+        }
+        notes
+    }
+
+    pub fn bass(&self) -> Note {
+        self.notes()[0]
+    }
+
+    /// `None` for the seventh of a triad
+    pub fn tone(&self, tone: ChordTone) -> Option<Note> {
+        let root_position = self.root_position();
+        let note = *root_position.get(tone.index())?;
+
+        // where it sits in the voicing
+        match tone.index() < self.inversion as usize {
+            true => Some(Note { octave: note.octave + 1, ..note }),
+            false => Some(note),
+        }
+    }
+}
+
+impl ChordTone {
+    fn index(&self) -> usize {
+        match self {
+            ChordTone::Root => 0,
+            ChordTone::Third => 1,
+            ChordTone::Fifth => 2,
+            ChordTone::Seventh => 3,
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "root" => Ok(ChordTone::Root),
+            "third" => Ok(ChordTone::Third),
+            "fifth" => Ok(ChordTone::Fifth),
+            "seventh" => Ok(ChordTone::Seventh),
+            _ => Err(format!(
+                "invalid chord tone: {string} (expected root, third, fifth or seventh)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ChordQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            ChordQuality::Major => "major",
+            ChordQuality::Minor => "minor",
+            ChordQuality::Diminished => "diminished",
+            ChordQuality::Augmented => "augmented",
+            ChordQuality::DominantSeventh => "dominant seventh",
+            ChordQuality::MajorSeventh => "major seventh",
+            ChordQuality::MinorSeventh => "minor seventh",
+            ChordQuality::HalfDiminishedSeventh => "half-diminished seventh",
+            ChordQuality::DiminishedSeventh => "diminished seventh",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl fmt::Display for ChordTone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            ChordTone::Root => "root",
+            ChordTone::Third => "third",
+            ChordTone::Fifth => "fifth",
+            ChordTone::Seventh => "seventh",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inversion = match self.inversion {
+            0 => "root position",
+            1 => "first inversion",
+            2 => "second inversion",
+            _ => "third inversion",
+        };
+        write!(
+            f,
+            "{} {}, {}",
+            self.root.spelling(),
+            self.quality,
+            inversion
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(chord: &Chord) -> String {
+        chord
+            .notes()
+            .iter()
+            .map(|note| note.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn chord(root: &str, quality: ChordQuality, inversion: u8) -> Chord {
+        Chord::new(Note::parse_from_string(root).unwrap(), quality, inversion).unwrap()
+    }
+
+    #[test]
+    fn chords_are_stacked_thirds() {
+        assert_eq!(notes(&chord("C4", ChordQuality::Major, 0)), "C4 E4 G4");
+        assert_eq!(notes(&chord("F#3", ChordQuality::Minor, 0)), "F#3 A3 C#4");
+        assert_eq!(notes(&chord("B3", ChordQuality::Diminished, 0)), "B3 D4 F4");
+        assert_eq!(
+            notes(&chord("Ab3", ChordQuality::Augmented, 0)),
+            "Ab3 C4 E4"
+        );
+        assert_eq!(
+            notes(&chord("G3", ChordQuality::DominantSeventh, 0)),
+            "G3 B3 D4 F4"
+        );
+        assert_eq!(
+            notes(&chord("C#4", ChordQuality::DiminishedSeventh, 0)),
+            "C#4 E4 G4 Bb4"
+        );
+    }
+
+    #[test]
+    fn roots_spelled_in_a_key_need_at_most_double_accidentals() {
+        use crate::music::key::{Key, Mode};
+
+        let gb = NamingPolicy::Key(Key::parse("Gb").unwrap());
+        let b_dim7 = Chord::spelled(SimpleNote::new(71), ChordQuality::DiminishedSeventh, 0, gb);
+        assert_eq!(notes(&b_dim7.unwrap()), "B4 D5 F5 Ab5");
+
+        let tonics = [
+            NoteName::C,
+            NoteName::D,
+            NoteName::E,
+            NoteName::F,
+            NoteName::G,
+            NoteName::A,
+            NoteName::B,
+        ];
+        for tonic in tonics {
+            for alteration in -1..=1 {
+                for mode in Mode::all() {
+                    let Ok(key) = Key::new(tonic, alteration, mode) else {
+                        continue;
+                    };
+                    for root in 60..72 {
+                        for quality in ChordQuality::all() {
+                            let chord = Chord::spelled(
+                                SimpleNote::new(root),
+                                quality,
+                                0,
+                                NamingPolicy::Key(key),
+                            )
+                            .unwrap();
+                            assert!(
+                                chord.notes().iter().all(|note| note.alteration.abs() <= 2),
+                                "{chord} in {key}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inversions() {
+        assert_eq!(notes(&chord("C4", ChordQuality::Major, 1)), "E4 G4 C5");
+        assert_eq!(notes(&chord("C4", ChordQuality::Major, 2)), "G4 C5 E5");
+        let third_inversion = chord("G3", ChordQuality::DominantSeventh, 3);
+        assert_eq!(notes(&third_inversion), "F4 G4 B4 D5");
+        assert_eq!(
+            third_inversion.tone(ChordTone::Third).unwrap().to_string(),
+            "B4"
+        );
+        assert_eq!(
+            third_inversion
+                .tone(ChordTone::Seventh)
+                .unwrap()
+                .to_string(),
+            "F4"
+        );
+        assert_eq!(
+            chord("C4", ChordQuality::Major, 0).tone(ChordTone::Seventh),
+            None
+        );
+        assert!(Chord::new(third_inversion.root, ChordQuality::Major, 3).is_err());
+    }
+}
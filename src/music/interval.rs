@@ -3,6 +3,8 @@ use std::fmt;
 
 use rand::thread_rng;
 
+use crate::music::Note;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Interval {
     pub base_interval: BaseInterval,
@@ -19,6 +21,14 @@ pub enum BaseInterval {
     Fifth = 4,
     Sixth = 5,
     Seventh = 6,
+    Octave = 7,
+    Ninth = 8,
+    Tenth = 9,
+    Eleventh = 10,
+    Twelfth = 11,
+    Thirteenth = 12,
+    Fourteenth = 13,
+    Fifteenth = 14,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -31,21 +41,137 @@ pub enum Quality {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-#[allow(dead_code)]
 pub enum Direction {
     Up,
     Down,
 }
 
+/// Whether the two notes are played one after the other or together
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Presentation {
+    #[default]
+    Melodic,
+    Harmonic,
+}
+
 impl Interval {
     pub fn size_i8(&self) -> i8 {
-        self.base_interval.size_i8() + self.quality.delta()
+        self.base_interval.size_i8() + self.quality.delta(self.base_interval)
     }
 
-    pub fn get_random_diatonic() -> Interval {
-        use rand::prelude::*;
-        let mut rng = thread_rng();
-        let diatonic_intervals = [
+    /// Short notation, e.g. "m3", "P5", "A4" or "M10"
+    pub fn short_repr(&self) -> String {
+        format!(
+            "{}{}",
+            self.quality.short_repr(),
+            self.base_interval.index_i8() + 1
+        )
+    }
+
+    pub fn parse_from_string(string: &str) -> Result<Interval, &'static str> {
+        let mut chars = string.chars();
+
+        let quality = match chars.next() {
+            None => return Err("no interval given"),
+            Some(c) => match c {
+                'M' => Quality::Major,
+                'm' => Quality::Minor,
+                'P' => Quality::Perfect,
+                'd' => Quality::Diminished,
+                'A' => Quality::Augmented,
+                _ => return Err("invalid interval quality"),
+            },
+        };
+
+        let number: u8 = match chars.as_str().parse() {
+            Ok(n @ 1..=15) => n,
+            _ => return Err("invalid interval number"),
+        };
+        let base_interval = BaseInterval::try_from(number - 1).unwrap();
+
+        let interval = Interval { base_interval, quality };
+        match Interval::all().contains(&interval) {
+            true => Ok(interval),
+            false => Err("invalid interval quality"),
+        }
+    }
+
+    /// The usual spelling of an interval within an octave, the tritone being an augmented fourth
+    pub fn from_semitones(semitones: i8) -> Interval {
+        let (base_interval, quality) = match semitones.rem_euclid(12) {
+            0 => (BaseInterval::Unison, Quality::Perfect),
+            1 => (BaseInterval::Second, Quality::Minor),
+            2 => (BaseInterval::Second, Quality::Major),
+            3 => (BaseInterval::Third, Quality::Minor),
+            4 => (BaseInterval::Third, Quality::Major),
+            5 => (BaseInterval::Fourth, Quality::Perfect),
+            6 => (BaseInterval::Fourth, Quality::Augmented),
+            7 => (BaseInterval::Fifth, Quality::Perfect),
+            8 => (BaseInterval::Sixth, Quality::Minor),
+            9 => (BaseInterval::Sixth, Quality::Major),
+            10 => (BaseInterval::Seventh, Quality::Minor),
+            11 => (BaseInterval::Seventh, Quality::Major),
+            _ => panic!("unreachable"),
+        };
+
+        Interval { base_interval, quality }
+    }
+
+    /// Between two spelled notes in either order, compounds included, e.g. a diminished fifth
+    /// from B to F or a major tenth from C to E an octave up. `None` past the double octave, or
+    /// for spellings we don't name such as a doubly augmented fourth
+    pub fn between(from: Note, to: Note) -> Option<Interval> {
+        let staff_position = |note: Note| 7 * note.octave + note.name.index_i8();
+        let steps = staff_position(to) - staff_position(from);
+        let semitones = to.chromatic_distance_up_from(from);
+        // C to C# and C to Cb are both augmented unisons
+        let (steps, semitones) = match steps {
+            0 => (0, semitones.abs()),
+            _ => (steps.abs(), semitones * steps.signum()),
+        };
+
+        let base_interval = BaseInterval::try_from(u8::try_from(steps).ok()?).ok()?;
+        let quality = match (
+            semitones - base_interval.size_i8(),
+            base_interval.is_perfect(),
+        ) {
+            (0, true) => Quality::Perfect,
+            (0, false) => Quality::Major,
+            (-1, false) => Quality::Minor,
+            (-1, true) | (-2, false) => Quality::Diminished,
+            (1, _) => Quality::Augmented,
+            _ => return None,
+        };
+
+        let interval = Interval { base_interval, quality };
+        Interval::all().contains(&interval).then_some(interval)
+    }
+
+    /// Every spelling we know how to name, from the unison to the double octave
+    pub fn all() -> Vec<Interval> {
+        let mut intervals = Vec::new();
+
+        for base_interval in (0..=14).map(|i| BaseInterval::try_from(i).unwrap()) {
+            let qualities: &[Quality] = match (base_interval.is_perfect(), base_interval) {
+                (true, BaseInterval::Unison) => &[Quality::Perfect, Quality::Augmented],
+                (true, _) => &[Quality::Diminished, Quality::Perfect, Quality::Augmented],
+                (false, _) => &[
+                    Quality::Diminished,
+                    Quality::Minor,
+                    Quality::Major,
+                    Quality::Augmented,
+                ],
+            };
+            for &quality in qualities {
+                intervals.push(Interval { base_interval, quality });
+            }
+        }
+
+        intervals
+    }
+
+    pub fn diatonic_intervals() -> Vec<Interval> {
+        vec![
             //Interval { base_interval: BaseInterval::Unison, quality: Quality::Perfect },
             Interval { base_interval: BaseInterval::Second, quality: Quality::Major },
             Interval { base_interval: BaseInterval::Second, quality: Quality::Minor },
@@ -59,13 +185,30 @@ impl Interval {
             Interval { base_interval: BaseInterval::Sixth, quality: Quality::Minor },
             Interval { base_interval: BaseInterval::Seventh, quality: Quality::Major },
             Interval { base_interval: BaseInterval::Seventh, quality: Quality::Minor },
-        ];
+        ]
+    }
 
-        *diatonic_intervals.choose(&mut rng).unwrap()
+    /// The octave and the usual extensions up to the double octave
+    pub fn compound_intervals() -> Vec<Interval> {
+        vec![
+            Interval { base_interval: BaseInterval::Octave, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Ninth, quality: Quality::Major },
+            Interval { base_interval: BaseInterval::Ninth, quality: Quality::Minor },
+            Interval { base_interval: BaseInterval::Ninth, quality: Quality::Augmented },
+            Interval { base_interval: BaseInterval::Tenth, quality: Quality::Major },
+            Interval { base_interval: BaseInterval::Tenth, quality: Quality::Minor },
+            Interval { base_interval: BaseInterval::Eleventh, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Eleventh, quality: Quality::Augmented },
+            Interval { base_interval: BaseInterval::Twelfth, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Thirteenth, quality: Quality::Major },
+            Interval { base_interval: BaseInterval::Thirteenth, quality: Quality::Minor },
+            Interval { base_interval: BaseInterval::Fourteenth, quality: Quality::Major },
+            Interval { base_interval: BaseInterval::Fourteenth, quality: Quality::Minor },
+            Interval { base_interval: BaseInterval::Fifteenth, quality: Quality::Perfect },
+        ]
     }
 }
 
-#[allow(dead_code)]
 impl Direction {
     pub fn rand() -> Self {
         use rand::Rng;
@@ -78,9 +221,32 @@ impl Direction {
             _ => panic!("unreachable"),
         }
     }
+
+    pub fn parse_from_string(string: &str) -> Result<Direction, &'static str> {
+        match string {
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            _ => Err("invalid direction"),
+        }
+    }
+}
+
+impl Presentation {
+    pub fn parse_from_string(string: &str) -> Result<Presentation, &'static str> {
+        match string {
+            "melodic" => Ok(Presentation::Melodic),
+            "harmonic" => Ok(Presentation::Harmonic),
+            _ => Err("invalid presentation"),
+        }
+    }
 }
 
 impl BaseInterval {
+    /// unisons, fourths, fifths, octaves and their compounds
+    pub fn is_perfect(&self) -> bool {
+        matches!(self.index_i8() % 7, 0 | 3 | 4)
+    }
+
     pub fn size_i8(&self) -> i8 {
         match &self {
             BaseInterval::Unison => 0,
@@ -90,22 +256,46 @@ impl BaseInterval {
             BaseInterval::Fifth => 7,
             BaseInterval::Sixth => 9,
             BaseInterval::Seventh => 11,
+            BaseInterval::Octave => 12,
+            BaseInterval::Ninth => 14,
+            BaseInterval::Tenth => 16,
+            BaseInterval::Eleventh => 17,
+            BaseInterval::Twelfth => 19,
+            BaseInterval::Thirteenth => 21,
+            BaseInterval::Fourteenth => 23,
+            BaseInterval::Fifteenth => 24,
         }
     }
 
     pub fn size_u8(&self) -> u8 {
         self.size_i8().try_into().unwrap()
     }
+
+    pub fn index_i8(&self) -> i8 {
+        u8::from(*self).try_into().unwrap()
+    }
 }
 
 impl Quality {
-    pub fn delta(&self) -> i8 {
+    /// semitones away from the major or perfect interval
+    pub fn delta(&self, base_interval: BaseInterval) -> i8 {
+        match (&self, base_interval.is_perfect()) {
+            (Quality::Major, _) => 0,
+            (Quality::Minor, _) => -1,
+            (Quality::Perfect, _) => 0,
+            (Quality::Diminished, true) => -1,
+            (Quality::Diminished, false) => -2,
+            (Quality::Augmented, _) => 1,
+        }
+    }
+
+    pub fn short_repr(&self) -> &'static str {
         match &self {
-            Quality::Major => 0,
-            Quality::Minor => -1,
-            Quality::Perfect => 0,
-            Quality::Diminished => -1,
-            Quality::Augmented => 1,
+            Quality::Major => "M",
+            Quality::Minor => "m",
+            Quality::Perfect => "P",
+            Quality::Diminished => "d",
+            Quality::Augmented => "A",
         }
     }
 }
@@ -115,6 +305,26 @@ impl fmt::Display for Interval {
         write!(f, "{} {}", self.quality, self.base_interval)
     }
 }
+impl fmt::Display for Presentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match &self {
+            Presentation::Melodic => "melodic",
+            Presentation::Harmonic => "harmonic",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match &self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
 impl fmt::Display for BaseInterval {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let repr = match &self {
@@ -125,6 +335,14 @@ impl fmt::Display for BaseInterval {
             BaseInterval::Fifth => "Fifth",
             BaseInterval::Sixth => "Sixth",
             BaseInterval::Seventh => "Seventh",
+            BaseInterval::Octave => "Octave",
+            BaseInterval::Ninth => "Ninth",
+            BaseInterval::Tenth => "Tenth",
+            BaseInterval::Eleventh => "Eleventh",
+            BaseInterval::Twelfth => "Twelfth",
+            BaseInterval::Thirteenth => "Thirteenth",
+            BaseInterval::Fourteenth => "Fourteenth",
+            BaseInterval::Fifteenth => "Fifteenth",
         };
         write!(f, "{}", repr)
     }
@@ -142,3 +360,25 @@ impl fmt::Display for Quality {
         write!(f, "{}", repr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn between(from: &str, to: &str) -> Option<String> {
+        let note = |string| Note::parse_from_string(string).unwrap();
+        Interval::between(note(from), note(to)).map(|interval| interval.short_repr())
+    }
+
+    #[test]
+    fn intervals_between_spelled_notes() {
+        assert_eq!(between("C4", "C5").as_deref(), Some("P8"));
+        assert_eq!(between("C5", "C4").as_deref(), Some("P8"));
+        assert_eq!(between("B4", "F5").as_deref(), Some("d5"));
+        assert_eq!(between("B4", "F4").as_deref(), Some("A4"));
+        assert_eq!(between("C4", "E5").as_deref(), Some("M10"));
+        assert_eq!(between("Eb4", "D#4").as_deref(), Some("d2"));
+        assert_eq!(between("C4", "C#4").as_deref(), Some("A1"));
+        assert_eq!(between("C3", "D5").as_deref(), None);
+    }
+}
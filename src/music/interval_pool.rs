@@ -0,0 +1,225 @@
+use std::fmt;
+
+use rand::distributions::{Distribution, WeightedIndex};
+
+use crate::music::interval::{BaseInterval, Interval, Quality};
+
+/// The intervals an exercise can be drawn from, each with a relative weight
+///
+/// An interval that isn't in the pool, or has a weight of 0, is never picked
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntervalPool {
+    entries: Vec<(Interval, u32)>,
+}
+
+impl IntervalPool {
+    pub fn empty() -> Self {
+        IntervalPool { entries: Vec::new() }
+    }
+
+    pub fn from_intervals(intervals: impl IntoIterator<Item = Interval>) -> Self {
+        let mut pool = IntervalPool::empty();
+        for interval in intervals {
+            pool.enable(interval);
+        }
+        pool
+    }
+
+    /// Seconds through sevenths, without the unison
+    pub fn diatonic() -> Self {
+        IntervalPool::from_intervals(Interval::diatonic_intervals())
+    }
+
+    pub fn compound() -> Self {
+        IntervalPool::from_intervals(Interval::compound_intervals())
+    }
+
+    /// Every spelling from the unison to the double octave
+    pub fn all() -> Self {
+        IntervalPool::from_intervals(Interval::all())
+    }
+
+    pub fn perfect_only() -> Self {
+        IntervalPool::from_intervals([
+            Interval { base_interval: BaseInterval::Unison, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Fourth, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Octave, quality: Quality::Perfect },
+        ])
+    }
+
+    /// The tritone in both spellings against its perfect neighbours
+    pub fn tritone_vs_fifth() -> Self {
+        IntervalPool::from_intervals([
+            Interval { base_interval: BaseInterval::Fourth, quality: Quality::Perfect },
+            Interval { base_interval: BaseInterval::Fourth, quality: Quality::Augmented },
+            Interval { base_interval: BaseInterval::Fifth, quality: Quality::Diminished },
+            Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect },
+        ])
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "diatonic" => Some(IntervalPool::diatonic()),
+            "compound" => Some(IntervalPool::compound()),
+            "all" => Some(IntervalPool::all()),
+            "perfect" => Some(IntervalPool::perfect_only()),
+            "tritone-vs-fifth" => Some(IntervalPool::tritone_vs_fifth()),
+            _ => None,
+        }
+    }
+
+    /// Either a preset name or a comma separated list of intervals with optional weights,
+    /// e.g. "P4,P5:3,A4:2"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        if let Some(pool) = IntervalPool::preset(string) {
+            return Ok(pool);
+        }
+
+        let mut pool = IntervalPool::empty();
+        for item in string.split(',') {
+            let (interval, weight) = match item.split_once(':') {
+                Some((interval, weight)) => {
+                    let weight = weight
+                        .parse::<u32>()
+                        .map_err(|_| format!("invalid weight for {interval}: {weight}"))?;
+                    (interval, weight)
+                }
+                None => (item, 1),
+            };
+            let interval =
+                Interval::parse_from_string(interval).map_err(|e| format!("{e}: {interval}"))?;
+            pool.set_weight(interval, weight);
+        }
+
+        match pool.is_empty() {
+            true => Err(format!("no interval enabled in {string}")),
+            false => Ok(pool),
+        }
+    }
+
+    /// Adds the interval with a weight of 1, leaves its weight alone if it's already enabled
+    pub fn enable(&mut self, interval: Interval) {
+        if self.weight(interval) == 0 {
+            self.set_weight(interval, 1);
+        }
+    }
+
+    pub fn disable(&mut self, interval: Interval) {
+        self.entries.retain(|(i, _)| *i != interval);
+    }
+
+    pub fn set_weight(&mut self, interval: Interval, weight: u32) {
+        match self.entries.iter_mut().find(|(i, _)| *i == interval) {
+            Some(entry) => entry.1 = weight,
+            None => self.entries.push((interval, weight)),
+        }
+    }
+
+    pub fn weight(&self, interval: Interval) -> u32 {
+        self.entries
+            .iter()
+            .find(|(i, _)| *i == interval)
+            .map_or(0, |(_, weight)| *weight)
+    }
+
+    pub fn extend(&mut self, other: &IntervalPool) {
+        for &(interval, weight) in &other.entries {
+            self.set_weight(interval, self.weight(interval).max(weight));
+        }
+    }
+
+    pub fn intervals(&self) -> impl Iterator<Item = Interval> + '_ {
+        self.entries
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(interval, _)| *interval)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals().next().is_none()
+    }
+
+    /// Weighted pick among the enabled intervals that pass `filter`
+    pub fn choose(&self, filter: impl Fn(&Interval) -> bool) -> Option<Interval> {
+        self.choose_scaled(|interval| match filter(interval) {
+            true => 1.0,
+            false => 0.0,
+        })
+    }
+
+    /// Weighted pick where the pool weight of each interval is multiplied by `scale`,
+    /// a scale of 0 excludes the interval
+    pub fn choose_scaled(&self, scale: impl Fn(&Interval) -> f64) -> Option<Interval> {
+        let weights: Vec<f64> = self
+            .entries
+            .iter()
+            .map(|(interval, weight)| match weight {
+                0 => 0.0,
+                _ => *weight as f64 * scale(interval).max(0.0),
+            })
+            .collect();
+
+        let distribution = WeightedIndex::new(&weights).ok()?;
+        let index = distribution.sample(&mut rand::thread_rng());
+        Some(self.entries[index].0)
+    }
+}
+
+impl Default for IntervalPool {
+    fn default() -> Self {
+        IntervalPool::diatonic()
+    }
+}
+
+impl fmt::Display for IntervalPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(interval, weight)| match weight {
+                1 => interval.short_repr(),
+                _ => format!("{}:{}", interval.short_repr(), weight),
+            })
+            .collect();
+        write!(f, "{}", repr.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIFTH: Interval =
+        Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect };
+    const TRITONE: Interval =
+        Interval { base_interval: BaseInterval::Fourth, quality: Quality::Augmented };
+
+    #[test]
+    fn parse_list_with_weights() {
+        let pool = IntervalPool::parse("P5:3,A4").unwrap();
+
+        assert_eq!(pool.weight(FIFTH), 3);
+        assert_eq!(pool.weight(TRITONE), 1);
+        assert_eq!(pool.to_string(), "P5:3,A4");
+        assert!(IntervalPool::parse("P3").is_err());
+        assert!(IntervalPool::parse("P5:0").is_err());
+    }
+
+    #[test]
+    fn disabled_intervals_are_never_chosen() {
+        let mut pool = IntervalPool::tritone_vs_fifth();
+        pool.disable(TRITONE);
+        pool.set_weight(
+            Interval { base_interval: BaseInterval::Fifth, quality: Quality::Diminished },
+            0,
+        );
+
+        for _ in 0..100 {
+            let interval = pool.choose(|interval| interval.size_i8() > 5).unwrap();
+            assert_eq!(interval, FIFTH);
+        }
+        assert_eq!(pool.choose(|interval| interval.size_i8() > 7), None);
+    }
+}
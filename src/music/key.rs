@@ -0,0 +1,322 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use rand::seq::SliceRandom;
+
+use crate::music::{note::NoteName, Note, SimpleNote};
+
+/// Semitones from the tonic to every degree of the major scale
+pub const MAJOR_SCALE: [i8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// The modes of the major scale
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Major,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Minor,
+    Locrian,
+}
+
+/// A tonic and a mode, e.g. F# minor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Key {
+    pub tonic: NoteName,
+    pub alteration: i8,
+    pub mode: Mode,
+}
+
+impl Mode {
+    pub fn all() -> [Mode; 7] {
+        [
+            Mode::Major,
+            Mode::Dorian,
+            Mode::Phrygian,
+            Mode::Lydian,
+            Mode::Mixolydian,
+            Mode::Minor,
+            Mode::Locrian,
+        ]
+    }
+
+    /// Semitones from the tonic to every degree of the scale
+    pub fn steps(&self) -> [i8; 7] {
+        let start = Mode::all().iter().position(|mode| mode == self).unwrap();
+        std::array::from_fn(|degree| {
+            (MAJOR_SCALE[(start + degree) % 7] - MAJOR_SCALE[start]).rem_euclid(12)
+        })
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        Mode::all()
+            .into_iter()
+            .find(|mode| mode.to_string() == string)
+            .ok_or(format!(
+                "invalid mode: {string} (expected {})",
+                Mode::all().map(|m| m.to_string()).join(", ")
+            ))
+    }
+}
+
+impl Key {
+    /// Fails for keys that would need double sharps or flats, e.g. G# major
+    pub fn new(tonic: NoteName, alteration: i8, mode: Mode) -> Result<Self, String> {
+        let key = Key { tonic, alteration, mode };
+
+        match note_names().any(|name| key.alteration_of(name).abs() > 1) {
+            true => Err(format!(
+                "{key} has no key signature, try its enharmonic key"
+            )),
+            false => Ok(key),
+        }
+    }
+
+    /// How the key signature alters a note name, e.g. 1 for F in G major
+    pub fn alteration_of(&self, name: NoteName) -> i8 {
+        let degree = (name.index_i8() - self.tonic.index_i8()).rem_euclid(7) as usize;
+        let pitch_class = self.tonic.semitones_from_c() as i8 + self.alteration;
+        let pitch_class = pitch_class + self.mode.steps()[degree];
+
+        match (pitch_class - name.semitones_from_c() as i8).rem_euclid(12) {
+            alteration @ 0..=6 => alteration,
+            alteration => alteration - 12,
+        }
+    }
+
+    /// Number of sharps, or minus the number of flats
+    pub fn signature(&self) -> i8 {
+        note_names().map(|name| self.alteration_of(name)).sum()
+    }
+
+    pub fn contains(&self, note: SimpleNote) -> bool {
+        note_names().any(|name| {
+            let pitch_class = name.semitones_from_c() as i8 + self.alteration_of(name);
+            pitch_class.rem_euclid(12) == note.get_i8() % 12
+        })
+    }
+
+    /// As written in the key, or with the fewest accidentals if the note isn't in the key,
+    /// sharps in sharp keys and flats in flat keys
+    pub fn spell(&self, note: SimpleNote) -> Note {
+        let sharp_key = self.signature() >= 0;
+
+        let (name, alteration) = note_names()
+            .filter_map(|name| {
+                let alteration =
+                    match (note.get_i8() - name.semitones_from_c() as i8).rem_euclid(12) {
+                        alteration @ 0..=2 => alteration,
+                        alteration @ 10..=11 => alteration - 12,
+                        _ => return None,
+                    };
+                Some((name, alteration))
+            })
+            .min_by_key(|(name, alteration)| {
+                let accidental = alteration - self.alteration_of(*name);
+                let against_the_key = match accidental.cmp(&0) {
+                    Ordering::Greater => !sharp_key,
+                    Ordering::Less => sharp_key,
+                    Ordering::Equal => false,
+                };
+                (accidental.abs(), alteration.abs(), against_the_key)
+            })
+            .unwrap();
+
+        let octave = (note.get_i8() - name.semitones_from_c() as i8 - alteration).div_euclid(12);
+        Note { name, alteration, octave: octave - 1 }
+    }
+
+    /// The accidental to write in front of the note, if the key signature or an earlier note
+    /// on the same line or space doesn't already say it, 0 being a natural
+    pub fn accidental(&self, note: Note, earlier_note: Option<Note>) -> Option<i8> {
+        let prevailing = match earlier_note {
+            Some(earlier) if (earlier.name, earlier.octave) == (note.name, note.octave) => {
+                earlier.alteration
+            }
+            _ => self.alteration_of(note.name),
+        };
+
+        (note.alteration != prevailing).then_some(note.alteration)
+    }
+
+    /// The major keys with at most 6 sharps or flats
+    pub fn majors() -> Vec<Self> {
+        [
+            "C", "G", "D", "A", "E", "B", "F#", "Db", "Ab", "Eb", "Bb", "F",
+        ]
+        .into_iter()
+        .map(|tonic| Key::parse(tonic).unwrap())
+        .collect()
+    }
+
+    /// One of `Key::majors`
+    pub fn rand_major() -> Self {
+        *Key::majors().choose(&mut rand::thread_rng()).unwrap()
+    }
+
+    /// I IV V I, close to the tonic: the IV over the tonic and the V over the leading tone.
+    /// Minor keys get a major V. Only major and minor keys have one, the other modes would
+    /// get a diminished or minor V that doesn't lead anywhere
+    pub fn cadence(&self, tonic: Note) -> [Vec<Note>; 4] {
+        debug_assert!(self.has_cadence(), "no cadence in {self}");
+        let steps = self.mode.steps();
+        let tonic = tonic.to_simple();
+        // scale degrees from the tonic, 0 being the tonic itself
+        let chord = |degrees: [i8; 3]| {
+            degrees
+                .into_iter()
+                .map(|degree| {
+                    let mut semitones =
+                        steps[degree.rem_euclid(7) as usize] + 12 * degree.div_euclid(7);
+                    if self.mode == Mode::Minor && degree == -1 {
+                        semitones += 1;
+                    }
+                    self.spell(tonic.shift(semitones))
+                })
+                .collect::<Vec<Note>>()
+        };
+
+        let one = chord([0, 2, 4]);
+        [one.clone(), chord([0, 3, 5]), chord([-1, 1, 4]), one]
+    }
+
+    pub fn has_cadence(&self) -> bool {
+        matches!(self.mode, Mode::Major | Mode::Minor)
+    }
+
+    /// The tonic and the mode, e.g. "Bb", "F#m" or "D-dorian"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        let error = || format!("invalid key: {string} (expected e.g. C, F#m, Bb or D-dorian)");
+
+        let (tonic, mode) = match string.split_once('-') {
+            Some((tonic, mode)) => (tonic, Mode::parse(mode)?),
+            None => match string.strip_suffix('m') {
+                Some(tonic) => (tonic, Mode::Minor),
+                None => (string, Mode::Major),
+            },
+        };
+        // any octave will do
+        let tonic = Note::parse_from_string(&format!("{tonic}4")).map_err(|_| error())?;
+
+        Key::new(tonic.name, tonic.alteration, mode)
+    }
+}
+
+impl Default for Key {
+    /// C major, where every accidental is written
+    fn default() -> Self {
+        Key { tonic: NoteName::C, alteration: 0, mode: Mode::Major }
+    }
+}
+
+fn note_names() -> impl Iterator<Item = NoteName> {
+    (0..7).map(|index| NoteName::try_from(index).unwrap())
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Mode::Major => "major",
+            Mode::Dorian => "dorian",
+            Mode::Phrygian => "phrygian",
+            Mode::Lydian => "lydian",
+            Mode::Mixolydian => "mixolydian",
+            Mode::Minor => "minor",
+            Mode::Locrian => "locrian",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alteration = match self.alteration {
+            -2 => "bb",
+            -1 => "b",
+            0 => "",
+            1 => "#",
+            2 => "##",
+            _ => "X",
+        };
+        write!(f, "{}{} {}", self.tonic, alteration, self.mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(string: &str) -> Key {
+        Key::parse(string).unwrap()
+    }
+
+    #[test]
+    fn key_signatures() {
+        assert_eq!(key("C").signature(), 0);
+        assert_eq!(key("Am").signature(), 0);
+        assert_eq!(key("D-dorian").signature(), 0);
+        assert_eq!(key("F#m").signature(), 3);
+        assert_eq!(key("Bb").signature(), -2);
+        assert_eq!(key("C#").signature(), 7);
+        assert_eq!(key("Eb-lydian").signature(), -2);
+        assert_eq!(key("Bb").alteration_of(NoteName::E), -1);
+        assert!(Key::parse("G#").is_err());
+        assert!(Key::parse("H").is_err());
+    }
+
+    #[test]
+    fn notes_are_spelled_in_the_key() {
+        let spell = |key_name: &str, note: &str| {
+            let note = Note::parse_from_string(note).unwrap().to_simple();
+            key(key_name).spell(note).to_string()
+        };
+
+        assert_eq!(spell("F#m", "Db4"), "C#4");
+        assert_eq!(spell("Eb", "G#3"), "Ab3");
+        assert_eq!(spell("C#", "C4"), "B#3");
+        assert_eq!(spell("Gb", "B4"), "Cb5");
+        // out of the key
+        assert_eq!(spell("F", "B4"), "B4");
+        assert_eq!(spell("C", "C#4"), "C#4");
+        assert_eq!(spell("Bb", "F#4"), "Gb4");
+    }
+
+    #[test]
+    fn accidentals_relative_to_the_key() {
+        let note = |string: &str| Note::parse_from_string(string).unwrap();
+        let d_major = key("D");
+
+        assert_eq!(d_major.accidental(note("F#4"), None), None);
+        assert_eq!(d_major.accidental(note("F4"), None), Some(0));
+        assert_eq!(d_major.accidental(note("Bb4"), None), Some(-1));
+        assert_eq!(d_major.accidental(note("F#4"), Some(note("F4"))), Some(1));
+        assert_eq!(d_major.accidental(note("F#4"), Some(note("F5"))), None);
+    }
+
+    #[test]
+    fn minor_cadences_have_a_leading_tone() {
+        let a3 = Note::parse_from_string("A3").unwrap();
+        let cadence = key("Am").cadence(a3).map(|chord| {
+            chord
+                .iter()
+                .map(|note| note.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+
+        assert_eq!(cadence, ["A3 C4 E4", "A3 D4 F4", "G#3 B3 E4", "A3 C4 E4"]);
+    }
+
+    #[test]
+    fn in_key_notes() {
+        let d_dorian = key("D-dorian");
+        let in_key = (60..72)
+            .filter(|&data| d_dorian.contains(SimpleNote::new(data)))
+            .count();
+
+        assert_eq!(in_key, 7);
+        assert!(!d_dorian.contains(Note::parse_from_string("F#4").unwrap().to_simple()));
+    }
+}
@@ -0,0 +1,73 @@
+use rand::Rng;
+
+use crate::music::{Key, Note, NoteRange, SimpleNote};
+
+/// A short tune in a key, to be sung back note by note
+#[derive(Clone, Debug, PartialEq)]
+pub struct Melody {
+    pub key: Key,
+    pub notes: Vec<Note>,
+}
+
+impl Melody {
+    /// The notes of the key within the range, a melody needs two of them
+    pub fn scale(range: &NoteRange, key: Key) -> Vec<Note> {
+        (range.bottom.get_i8()..=range.top.get_i8())
+            .map(SimpleNote::new)
+            .filter(|note| key.contains(*note))
+            .map(|note| key.spell(note))
+            .collect()
+    }
+
+    /// Notes of the key within the range, moving by 1 to `max_steps` steps of the scale at a
+    /// time, never twice the same note in a row
+    pub fn rand(range: &NoteRange, key: Key, length: usize, max_steps: u8) -> Result<Self, String> {
+        let scale = Melody::scale(range, key);
+        if scale.len() < 2 {
+            return Err(format!("the note range has less than two notes of {key}"));
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut index = rng.gen_range(0..scale.len());
+        let mut notes = vec![scale[index]];
+        while notes.len() < length {
+            // the steps that stay in the range, down then up
+            let max_steps = max_steps.max(1) as usize;
+            let down = index.min(max_steps);
+            let up = (scale.len() - 1 - index).min(max_steps);
+
+            let step = rng.gen_range(0..down + up);
+            index = match step < down {
+                true => index - 1 - step,
+                false => index + 1 + step - down,
+            };
+            notes.push(scale[index]);
+        }
+
+        Ok(Melody { key, notes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepwise_melodies_stay_in_the_key_and_range() {
+        let range = NoteRange::from_str("C4", "G4").unwrap();
+        let key = Key::parse("F").unwrap();
+
+        for _ in 0..20 {
+            let melody = Melody::rand(&range, key, 6, 1).unwrap();
+            assert_eq!(melody.notes.len(), 6);
+            for pair in melody.notes.windows(2) {
+                assert_eq!(Note::diatonic_distance(pair[0], pair[1]).abs(), 1);
+            }
+            for note in melody.notes {
+                assert!(key.contains(note.to_simple()));
+                assert!(note.to_simple().get_i8() >= range.bottom.get_i8());
+                assert!(note.to_simple().get_i8() <= range.top.get_i8());
+            }
+        }
+    }
+}
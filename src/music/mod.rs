@@ -1,9 +1,19 @@
+pub mod chord;
 pub mod interval;
+pub mod interval_pool;
+pub mod key;
+pub mod melody;
 pub mod note;
 pub mod note_range;
+pub mod scale_degree;
 pub mod simple_note;
 
-pub use interval::{Direction, Interval};
+pub use chord::{Chord, ChordQuality, ChordTone};
+pub use interval::{Direction, Interval, Presentation};
+pub use interval_pool::IntervalPool;
+pub use key::Key;
+pub use melody::Melody;
 pub use note::{Note, NoteName};
 pub use note_range::NoteRange;
-pub use simple_note::SimpleNote;
+pub use scale_degree::ScaleDegree;
+pub use simple_note::{NamingPolicy, SimpleNote};
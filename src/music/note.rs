@@ -2,9 +2,12 @@ use int_enum::IntEnum;
 use std::cmp::Ordering;
 use std::fmt;
 
-use crate::music::{interval::Interval, simple_note::SimpleNote};
+use crate::music::{
+    interval::{Direction, Interval},
+    simple_note::SimpleNote,
+};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Note {
     pub name: NoteName,
     pub alteration: i8,
@@ -37,6 +40,25 @@ impl Note {
         Note { name, alteration, octave }
     }
 
+    pub fn down(&self, interval: Interval) -> Note {
+        let new_notename: i8 = self.name.index_i8() - interval.base_interval.index_i8();
+        let octave_shift: i8 = new_notename.div_euclid(7);
+
+        let name: NoteName = NoteName::try_from(new_notename.rem_euclid(7) as u8).unwrap();
+        let octave: i8 = self.octave + octave_shift;
+        let alteration: i8 = self.chromatic_distance_up_from(Note { name, alteration: 0, octave })
+            - interval.size_i8();
+
+        Note { name, alteration, octave }
+    }
+
+    pub fn shift(&self, interval: Interval, direction: Direction) -> Note {
+        match direction {
+            Direction::Up => self.up(interval),
+            Direction::Down => self.down(interval),
+        }
+    }
+
     pub fn chromatic_distance_up_from(&self, other: Note) -> i8 {
         self.to_simple().get_i8() - other.to_simple().get_i8()
     }
@@ -61,6 +83,19 @@ impl Note {
         }
     }
 
+    /// Without the octave, e.g. "Eb"
+    pub fn spelling(&self) -> String {
+        let alteration_repr = match self.alteration {
+            -2 => "bb",
+            -1 => "b",
+            0 => "",
+            1 => "#",
+            2 => "##",
+            _ => "X",
+        };
+        format!("{}{}", self.name, alteration_repr)
+    }
+
     pub fn frequency(&self) -> f64 {
         let offset_from_a4: i8 = self.to_simple().get_i8() - 69;
 
@@ -133,6 +168,10 @@ impl NoteName {
         }
     }
 
+    pub fn index_i8(&self) -> i8 {
+        u8::from(*self).try_into().unwrap()
+    }
+
     // disgusting i know but there is no risk of panic
     pub fn diatonic_distance(from: Self, to: Self) -> i8 {
         <u8 as TryInto<i8>>::try_into(u8::from(to)).unwrap()
@@ -157,15 +196,7 @@ impl fmt::Display for NoteName {
 
 impl fmt::Display for Note {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let alteration_repr = match self.alteration {
-            -2 => "bb",
-            -1 => "b",
-            0 => "",
-            1 => "#",
-            2 => "##",
-            _ => "X",
-        };
-        write!(f, "{}{}{}", self.name, alteration_repr, self.octave)
+        write!(f, "{}{}", self.spelling(), self.octave)
     }
 }
 
@@ -215,4 +246,75 @@ mod tests {
         assert_eq!(Note::diatonic_distance(C6, D4), -(6 + 7));
         assert_eq!(Note::diatonic_distance(B6, C4), -(6 + 14));
     }
+
+    #[test]
+    fn interval_down_spelling() {
+        use crate::music::interval::{BaseInterval, Quality};
+
+        let minor_third = Interval { base_interval: BaseInterval::Third, quality: Quality::Minor };
+        let major_sixth = Interval { base_interval: BaseInterval::Sixth, quality: Quality::Major };
+        let tritone = Interval { base_interval: BaseInterval::Fourth, quality: Quality::Augmented };
+
+        assert_eq!(
+            C4.down(minor_third),
+            Note { name: NoteName::A, alteration: 0, octave: 3 }
+        );
+        assert_eq!(
+            D4.down(major_sixth),
+            Note { name: NoteName::F, alteration: 0, octave: 3 }
+        );
+        assert_eq!(
+            C4.down(tritone),
+            Note { name: NoteName::G, alteration: -1, octave: 3 }
+        );
+        assert_eq!(
+            A4.down(minor_third),
+            Note { name: NoteName::F, alteration: 1, octave: 4 }
+        );
+    }
+
+    #[test]
+    fn interval_down_is_inverse_of_up() {
+        use crate::music::interval::{BaseInterval, Quality};
+
+        let intervals = [
+            Interval { base_interval: BaseInterval::Second, quality: Quality::Minor },
+            Interval { base_interval: BaseInterval::Third, quality: Quality::Major },
+            Interval { base_interval: BaseInterval::Fifth, quality: Quality::Diminished },
+            Interval { base_interval: BaseInterval::Seventh, quality: Quality::Major },
+        ];
+        for note in [C4, D4, A4, B4, C5] {
+            for interval in intervals {
+                assert_eq!(note.up(interval).down(interval), note);
+                assert_eq!(note.down(interval).up(interval), note);
+            }
+        }
+    }
+
+    #[test]
+    fn compound_interval_spelling() {
+        use crate::music::interval::{BaseInterval, Quality};
+
+        let major_tenth = Interval { base_interval: BaseInterval::Tenth, quality: Quality::Major };
+        let minor_ninth = Interval { base_interval: BaseInterval::Ninth, quality: Quality::Minor };
+        let octave = Interval { base_interval: BaseInterval::Octave, quality: Quality::Perfect };
+        let eleventh =
+            Interval { base_interval: BaseInterval::Eleventh, quality: Quality::Perfect };
+
+        assert_eq!(
+            C4.up(major_tenth),
+            Note { name: NoteName::E, alteration: 0, octave: 5 }
+        );
+        assert_eq!(
+            B4.up(minor_ninth),
+            Note { name: NoteName::C, alteration: 0, octave: 6 }
+        );
+        assert_eq!(A4.up(octave), A5);
+        assert_eq!(D5.down(eleventh), A4.down(octave));
+        assert_eq!(
+            C6.down(major_tenth),
+            Note { name: NoteName::A, alteration: -1, octave: 4 }
+        );
+        assert_eq!(major_tenth.to_string(), "Major Tenth");
+    }
 }
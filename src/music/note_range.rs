@@ -1,6 +1,10 @@
-use crate::music::{note::Note, simple_note::SimpleNote};
+use crate::music::{
+    key::Key,
+    note::Note,
+    simple_note::{NamingPolicy, SimpleNote},
+};
 
-use rand::Rng;
+use rand::{seq::SliceRandom, Rng};
 use std::fmt;
 
 #[derive(Debug, Copy, Clone)]
@@ -17,16 +21,19 @@ impl NoteRange {
         }
     }
 
-    pub fn crop_bottom(&self, shift_amount: i8) -> Self {
-        assert!(shift_amount < self.size_i8());
-
-        NoteRange::new(self.bottom.shift(shift_amount), self.top).unwrap()
+    /// `None` if the range is too narrow to be cropped by that much
+    pub fn crop_bottom(&self, shift_amount: i8) -> Option<Self> {
+        NoteRange::new(self.bottom.shift(shift_amount), self.top).ok()
     }
 
-    pub fn crop_top(&self, shift_amount: i8) -> Self {
-        assert!(shift_amount < self.size_i8());
+    /// `None` if the range is too narrow to be cropped by that much
+    pub fn crop_top(&self, shift_amount: i8) -> Option<Self> {
+        NoteRange::new(self.bottom, self.top.shift(-shift_amount)).ok()
+    }
 
-        NoteRange::new(self.bottom, self.top.shift(-shift_amount)).unwrap()
+    /// whether both notes of an interval of this size fit in the range
+    pub fn fits(&self, interval_size: i8) -> bool {
+        self.crop_top(interval_size).is_some()
     }
 
     pub fn from_str(bottom_str: &str, top_str: &str) -> Result<Self, &'static str> {
@@ -38,12 +45,23 @@ impl NoteRange {
         NoteRange::new(bottom, top)
     }
 
-    pub fn rand(&self) -> Note {
+    pub fn rand(&self, policy: NamingPolicy) -> Note {
         let mut rng = rand::thread_rng();
         let rn: i8 = rng.gen_range(0..=self.size_i8());
         let note: i8 = self.bottom.get_i8() + rn;
 
-        SimpleNote::new(note).to_note_rand()
+        SimpleNote::new(note).spell(policy)
+    }
+
+    /// A note of the key
+    pub fn rand_in_key(&self, key: &Key, policy: NamingPolicy) -> Note {
+        let notes: Vec<SimpleNote> = (self.bottom.get_i8()..=self.top.get_i8())
+            .map(SimpleNote::new)
+            .filter(|note| key.contains(*note))
+            .collect();
+
+        // there are never two chromatic notes in a row, any range has a note of the key
+        notes.choose(&mut rand::thread_rng()).unwrap().spell(policy)
     }
 
     pub fn size_u8(&self) -> u8 {
@@ -54,8 +72,9 @@ impl NoteRange {
         self.top.get_i8() + 1 - self.bottom.get_i8()
     }
 
+    /// As it sounds, like every preset
     pub fn alto_sax() -> Self {
-        Self::from_str("Db3", "F6").unwrap()
+        Self::from_str("Db3", "Ab5").unwrap()
     }
 
     pub fn tenor_voice() -> Self {
@@ -65,6 +84,62 @@ impl NoteRange {
     pub fn treble_staff() -> Self {
         Self::from_str("E3", "A5").unwrap()
     }
+
+    pub fn bass_voice() -> Self {
+        Self::from_str("E2", "E4").unwrap()
+    }
+
+    pub fn viola() -> Self {
+        Self::from_str("C3", "E5").unwrap()
+    }
+
+    pub fn cello() -> Self {
+        Self::from_str("C2", "A4").unwrap()
+    }
+
+    /// Both hands around middle C
+    pub fn piano() -> Self {
+        Self::from_str("F2", "G5").unwrap()
+    }
+
+    /// A voice or an instrument, at concert pitch
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "treble" => Some(NoteRange::treble_staff()),
+            "tenor" => Some(NoteRange::tenor_voice()),
+            "bass" => Some(NoteRange::bass_voice()),
+            "alto-sax" => Some(NoteRange::alto_sax()),
+            "viola" => Some(NoteRange::viola()),
+            "cello" => Some(NoteRange::cello()),
+            "piano" => Some(NoteRange::piano()),
+            _ => None,
+        }
+    }
+
+    /// Either a preset, e.g. "bass", or two notes, e.g. "G2-C4"
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match NoteRange::preset(string) {
+            Some(range) => Ok(range),
+            None => {
+                let error = || {
+                    format!(
+                        "invalid range: {string} (expected treble, tenor, bass, alto-sax, viola, \
+                         cello, piano or two notes such as G2-C4)"
+                    )
+                };
+                let (bottom, top) = string.split_once('-').ok_or_else(error)?;
+                let bottom = Note::parse_from_string(bottom).map_err(|_| error())?;
+                let top = Note::parse_from_string(top).map_err(|_| error())?;
+                NoteRange::new(bottom.to_simple(), top.to_simple()).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+impl Default for NoteRange {
+    fn default() -> Self {
+        NoteRange::treble_staff()
+    }
 }
 
 impl fmt::Display for NoteRange {
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use crate::music::key::{Key, MAJOR_SCALE};
+use crate::music::{Note, NoteName};
+
+/// Where a note sits in a key, always counted from the major scale on the tonic, the way jazz
+/// musicians do: the third of C minor is b3
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScaleDegree {
+    /// from 1 to 7
+    pub degree: u8,
+    /// semitones away from the degree of the major scale
+    pub alteration: i8,
+}
+
+impl ScaleDegree {
+    pub fn of(note: Note, key: &Key) -> Self {
+        let degree = NoteName::diatonic_distance(key.tonic, note.name).rem_euclid(7);
+        let tonic = key.tonic.semitones_from_c() as i8 + key.alteration;
+        let semitones = (note.to_simple().get_i8() - tonic).rem_euclid(12);
+
+        let alteration = match semitones - MAJOR_SCALE[degree as usize] {
+            alteration @ -6..=6 => alteration,
+            alteration if alteration > 6 => alteration - 12,
+            alteration => alteration + 12,
+        };
+        ScaleDegree { degree: degree as u8 + 1, alteration }
+    }
+
+    /// Movable do, with chromatic syllables for single alterations
+    pub fn syllable(&self) -> Option<&'static str> {
+        const NATURAL: [&str; 7] = ["do", "re", "mi", "fa", "sol", "la", "ti"];
+        const RAISED: [&str; 7] = ["di", "ri", "mi", "fi", "si", "li", "ti"];
+        const LOWERED: [&str; 7] = ["do", "ra", "me", "fa", "se", "le", "te"];
+
+        let index = self.degree as usize - 1;
+        match self.alteration {
+            0 => Some(NATURAL[index]),
+            // E# and Fb, B# and Cb have no syllable of their own
+            1 if !matches!(self.degree, 3 | 7) => Some(RAISED[index]),
+            -1 if !matches!(self.degree, 1 | 4) => Some(LOWERED[index]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ScaleDegree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alteration = match self.alteration {
+            a if a > 0 => "#".repeat(a as usize),
+            a => "b".repeat(-a as usize),
+        };
+        match self.syllable() {
+            Some(syllable) => write!(f, "{syllable} ({alteration}{})", self.degree),
+            None => write!(f, "{alteration}{}", self.degree),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degree(note: &str, key: &str) -> String {
+        let note = Note::parse_from_string(note).unwrap();
+        ScaleDegree::of(note, &Key::parse(key).unwrap()).to_string()
+    }
+
+    #[test]
+    fn degrees_in_a_key() {
+        assert_eq!(degree("E4", "C"), "mi (3)");
+        assert_eq!(degree("C4", "C"), "do (1)");
+        assert_eq!(degree("F#4", "C"), "fi (#4)");
+        assert_eq!(degree("Eb4", "Cm"), "me (b3)");
+        assert_eq!(degree("D5", "Eb"), "ti (7)");
+        assert_eq!(degree("Bb3", "Eb"), "sol (5)");
+        assert_eq!(degree("Db4", "D"), "b1");
+    }
+}
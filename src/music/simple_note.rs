@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::music::key::Key;
 use crate::music::note::{Note, NoteName};
 use rand::Rng;
 
@@ -29,12 +30,70 @@ impl SimpleNote {
         self.data
     }
 
-    // spell(&self, policy: NamingPolicy) -> SpelledNote ?
-    // with to_note_rand == spell(NamingPolicy::Rand)
-    pub fn to_note_rand(self) -> Note {
-        let mut rng = rand::thread_rng();
-        let sharp: bool = rng.gen::<bool>();
+    pub fn spell(self, policy: NamingPolicy) -> Note {
+        match policy {
+            NamingPolicy::Sharps => self.to_note(true),
+            NamingPolicy::Flats => self.to_note(false),
+            NamingPolicy::Random => self.to_note(rand::thread_rng().gen::<bool>()),
+            NamingPolicy::Key(key) => key.spell(self),
+            NamingPolicy::FewestAccidentals => self.to_note(matches!(self.data % 12, 1 | 6)),
+        }
+    }
+
+    /// Spelled under the policy along with the notes built on it, e.g. the other note of an
+    /// interval or the rest of a chord. `FewestAccidentals` picks the spelling needing the fewest
+    /// accidentals over all of them, the other policies are only overruled when a note would
+    /// need more than a double accidental
+    pub fn spell_with(self, policy: NamingPolicy, built_on: impl Fn(Note) -> Vec<Note>) -> Note {
+        let fits = |note: Note| built_on(note).iter().all(|n| n.alteration.abs() <= 2);
+        if policy != NamingPolicy::FewestAccidentals {
+            let note = self.spell(policy);
+            if fits(note) {
+                return note;
+            }
+        }
+
+        let accidentals = |note: Note| {
+            note.alteration.abs()
+                + built_on(note)
+                    .iter()
+                    .map(|n| n.alteration.abs())
+                    .sum::<i8>()
+        };
+        // the first of equals is kept, the usual spelling
+        self.enharmonics()
+            .into_iter()
+            .min_by_key(|&note| (!fits(note), accidentals(note)))
+            .unwrap()
+    }
+
+    /// Every spelling with at most one accidental, the usual one first, e.g. Eb and D#, or C and
+    /// B#
+    pub fn enharmonics(self) -> Vec<Note> {
+        let usual = self.spell(NamingPolicy::FewestAccidentals);
+        let mut notes = vec![usual];
+        for name in [
+            NoteName::C,
+            NoteName::D,
+            NoteName::E,
+            NoteName::F,
+            NoteName::G,
+            NoteName::A,
+            NoteName::B,
+        ] {
+            for alteration in -1..=1 {
+                let from_c = self.data - name.semitones_from_c() as i8 - alteration;
+                let note = Note { name, alteration, octave: from_c.div_euclid(12) - 1 };
+                if from_c.rem_euclid(12) == 0 && note != usual {
+                    notes.push(note);
+                }
+            }
+        }
+        notes
+    }
 
+    /// black keys are spelled with sharps, or with flats
+    pub fn to_note(self, sharp: bool) -> Note {
         match self.data % 12 {
             0 => Note { name: NoteName::C, alteration: 0, octave: self.octave() },
             1 => match sharp {
@@ -68,10 +127,18 @@ impl SimpleNote {
     }
 }
 
+/// How black keys are spelled
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum NamingPolicy {
     Flats,
     Sharps,
     Random,
+    /// as written in the key, see `Key::spell`
+    Key(Key),
+    /// as few accidentals as possible, counting the notes built on the one being spelled, see
+    /// `SimpleNote::spell_with`. Alone, the usual spelling of each black key: C#, Eb, F#, Ab and
+    /// Bb
+    FewestAccidentals,
 }
 
 impl fmt::Display for SimpleNote {
@@ -95,3 +162,69 @@ impl fmt::Display for SimpleNote {
         write!(f, "{}{}", name, self.octave())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::interval::{Direction, Interval};
+
+    fn spell(data: i8, policy: NamingPolicy) -> String {
+        SimpleNote::new(data).spell(policy).to_string()
+    }
+
+    #[test]
+    fn spelling_policies() {
+        // D#4/Eb4
+        assert_eq!(spell(63, NamingPolicy::Sharps), "D#4");
+        assert_eq!(spell(63, NamingPolicy::Flats), "Eb4");
+        assert_eq!(spell(63, NamingPolicy::FewestAccidentals), "Eb4");
+        assert_eq!(spell(66, NamingPolicy::FewestAccidentals), "F#4");
+        assert_eq!(spell(62, NamingPolicy::Flats), "D4");
+
+        let e_major = NamingPolicy::Key(Key::parse("E").unwrap());
+        assert_eq!(spell(63, e_major), "D#4");
+    }
+
+    /// The reference and the note an interval away from it
+    fn pair(data: i8, policy: NamingPolicy, interval: &str, direction: Direction) -> String {
+        let interval = Interval::parse_from_string(interval).unwrap();
+        let reference = SimpleNote::new(data).spell_with(policy, |reference| {
+            vec![reference.shift(interval, direction)]
+        });
+        format!("{} {}", reference, reference.shift(interval, direction))
+    }
+
+    #[test]
+    fn fewest_accidentals_count_both_notes() {
+        let fewest = NamingPolicy::FewestAccidentals;
+        // rather than C# F##, D# F## or A# F###
+        assert_eq!(pair(61, fewest, "A4", Direction::Up), "Db4 G4");
+        assert_eq!(pair(63, fewest, "M3", Direction::Up), "Eb4 G4");
+        assert_eq!(pair(70, fewest, "A6", Direction::Up), "Bb4 G#5");
+        // rather than F# E#
+        assert_eq!(pair(66, fewest, "m2", Direction::Down), "Gb4 F4");
+        // the usual spelling when it's a tie
+        assert_eq!(pair(61, fewest, "P5", Direction::Up), "C#4 G#4");
+        assert_eq!(pair(64, fewest, "d4", Direction::Up), "E4 Ab4");
+        // rather than B A##, a white key may be better off spelled with an accidental
+        assert_eq!(pair(71, fewest, "A6", Direction::Up), "Cb5 A5");
+    }
+
+    #[test]
+    fn other_policies_avoid_triple_accidentals() {
+        assert_eq!(
+            pair(70, NamingPolicy::Sharps, "A6", Direction::Up),
+            "Bb4 G#5"
+        );
+        assert_eq!(
+            pair(70, NamingPolicy::Sharps, "P5", Direction::Up),
+            "A#4 E#5"
+        );
+        let c_sharp = NamingPolicy::Key(Key::parse("C#").unwrap());
+        assert_eq!(pair(65, c_sharp, "A2", Direction::Up), "F4 G#4");
+        for _ in 0..20 {
+            let spelled = pair(70, NamingPolicy::Random, "A6", Direction::Up);
+            assert_eq!(spelled, "Bb4 G#5");
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::music::{Note, NoteName, NoteRange, SimpleNote};
+
+/// Every clef is drawn on the same five line staff, only the notes on the lines change
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Clef {
+    #[default]
+    Treble,
+    /// an octave below the treble clef, for tenors and guitars
+    SubOctaveTreble,
+    Bass,
+    Alto,
+    Tenor,
+}
+
+impl Clef {
+    /// in order of preference when several clefs fit a range as well
+    pub fn all() -> [Clef; 5] {
+        [
+            Clef::Treble,
+            Clef::Bass,
+            Clef::SubOctaveTreble,
+            Clef::Alto,
+            Clef::Tenor,
+        ]
+    }
+
+    pub fn bottom_line_note(&self) -> Note {
+        match self {
+            Clef::Treble => Note { name: NoteName::E, alteration: 0, octave: 4 },
+            Clef::SubOctaveTreble => Note { name: NoteName::E, alteration: 0, octave: 3 },
+            Clef::Bass => Note { name: NoteName::G, alteration: 0, octave: 2 },
+            Clef::Alto => Note { name: NoteName::F, alteration: 0, octave: 3 },
+            Clef::Tenor => Note { name: NoteName::D, alteration: 0, octave: 3 },
+        }
+    }
+
+    pub fn staff_sprite(&self) -> &'static str {
+        match self {
+            Clef::Treble => "treble_staff.png",
+            Clef::SubOctaveTreble => "treble_8vb_staff.png",
+            Clef::Bass => "bass_staff.png",
+            Clef::Alto => "alto_staff.png",
+            Clef::Tenor => "tenor_staff.png",
+        }
+    }
+
+    /// In half spaces from the bottom line, the top line being 8
+    pub fn staff_position(&self, note: Note) -> i32 {
+        Note::diatonic_distance(self.bottom_line_note(), note).into()
+    }
+
+    /// How many ledger lines the note needs, negative below the staff
+    pub fn ledger_lines(&self, note: Note) -> i32 {
+        match self.staff_position(note) {
+            position @ i32::MIN..=-2 => position / 2,
+            position @ 10..=i32::MAX => (position - 8) / 2,
+            _ => 0,
+        }
+    }
+
+    /// Staff positions of the sharps or flats of a key signature, in the order they're written
+    pub fn key_signature_positions(&self, sharps: bool) -> [i32; 7] {
+        const TREBLE_SHARPS: [i32; 7] = [8, 5, 9, 6, 3, 7, 4];
+        const TREBLE_FLATS: [i32; 7] = [4, 7, 3, 6, 2, 5, 1];
+
+        let shift = match self {
+            Clef::Treble | Clef::SubOctaveTreble => 0,
+            Clef::Bass => -2,
+            Clef::Alto => -1,
+            Clef::Tenor => 1,
+        };
+        match (self, sharps) {
+            // the usual pattern would put F# above the staff
+            (Clef::Tenor, true) => [2, 6, 3, 7, 4, 8, 5],
+            (_, true) => TREBLE_SHARPS.map(|position| position + shift),
+            (_, false) => TREBLE_FLATS.map(|position| position + shift),
+        }
+    }
+
+    /// Ledger lines needed by the worse of both ends of the range
+    pub fn worst_ledger_lines(&self, range: &NoteRange) -> i32 {
+        let ledger_lines = |note: SimpleNote| self.ledger_lines(note.to_note(true)).abs();
+
+        ledger_lines(range.bottom).max(ledger_lines(range.top))
+    }
+
+    /// The clef that needs the fewest ledger lines for both ends of the range
+    pub fn for_range(range: &NoteRange) -> Clef {
+        Clef::all()
+            .into_iter()
+            .min_by_key(|clef| clef.worst_ledger_lines(range))
+            .unwrap()
+    }
+
+    /// The clef the instrument or voice of a range preset reads, see `NoteRange::parse`
+    pub fn for_instrument(preset: &str) -> Option<Clef> {
+        match preset {
+            "treble" | "alto-sax" => Some(Clef::Treble),
+            "tenor" => Some(Clef::SubOctaveTreble),
+            "bass" | "cello" => Some(Clef::Bass),
+            "viola" => Some(Clef::Alto),
+            _ => None,
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        Clef::all()
+            .into_iter()
+            .find(|clef| clef.to_string() == string)
+            .ok_or(format!(
+                "invalid clef: {string} (expected {})",
+                Clef::all().map(|c| c.to_string()).join(", ")
+            ))
+    }
+}
+
+impl fmt::Display for Clef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Clef::Treble => "treble",
+            Clef::SubOctaveTreble => "treble-8vb",
+            Clef::Bass => "bass",
+            Clef::Alto => "alto",
+            Clef::Tenor => "tenor",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn middle_c_on_every_clef() {
+        let c4 = Note::parse_from_string("C4").unwrap();
+
+        assert_eq!(Clef::Treble.ledger_lines(c4), -1);
+        assert_eq!(Clef::SubOctaveTreble.staff_position(c4), 5);
+        assert_eq!(Clef::Bass.ledger_lines(c4), 1);
+        assert_eq!(Clef::Alto.staff_position(c4), 4);
+        assert_eq!(Clef::Tenor.staff_position(c4), 6);
+    }
+
+    #[test]
+    fn clef_for_range() {
+        assert_eq!(Clef::for_range(&NoteRange::treble_staff()), Clef::Treble);
+        assert_eq!(Clef::for_range(&NoteRange::bass_voice()), Clef::Bass);
+        assert_eq!(Clef::for_range(&NoteRange::cello()), Clef::Bass);
+        assert_eq!(
+            Clef::for_range(&NoteRange::parse("C2-D3").unwrap()),
+            Clef::Bass
+        );
+        // a range alone can't tell a tenor from a bassoon
+        assert_eq!(Clef::for_range(&NoteRange::tenor_voice()), Clef::Tenor);
+        assert_eq!(Clef::for_instrument("tenor"), Some(Clef::SubOctaveTreble));
+    }
+}
@@ -0,0 +1,83 @@
+use sdl2::{pixels::Color, render::Canvas, ttf::Font, video::Window};
+
+use crate::audio::devices::DeviceChoice;
+use crate::interval_trainer::DeviceMenu;
+use crate::render::text::render_text;
+
+const WHITE: Color = Color::RGB(255, 255, 255);
+const BLACK: Color = Color::RGB(0, 0, 0);
+const GREY: Color = Color::RGB(150, 150, 150);
+const GREEN: Color = Color::RGB(60, 170, 80);
+
+const MARGIN: i32 = 20;
+const HEADER_Y: i32 = 12;
+const FIRST_ROW_Y: i32 = 42;
+const ROW_HEIGHT: i32 = 22;
+const COLUMN_WIDTH: i32 = 480;
+
+/// The input devices on the left and the output ones on the right, numbered in the list being
+/// picked from, the ones in use in green
+pub fn render_devices(
+    menu: &DeviceMenu,
+    input: Option<&DeviceChoice>,
+    output: Option<&DeviceChoice>,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(WHITE);
+    canvas.clear();
+
+    render_column(
+        "Input",
+        &menu.inputs,
+        input,
+        !menu.picking_output,
+        MARGIN,
+        font,
+        canvas,
+    )?;
+    render_column(
+        "Output",
+        &menu.outputs,
+        output,
+        menu.picking_output,
+        MARGIN + COLUMN_WIDTH,
+        font,
+        canvas,
+    )
+}
+
+fn render_column(
+    title: &str,
+    devices: &[DeviceChoice],
+    current: Option<&DeviceChoice>,
+    picking: bool,
+    x: i32,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    let color = |in_use: bool| match (in_use, picking) {
+        (true, _) => GREEN,
+        (false, true) => BLACK,
+        (false, false) => GREY,
+    };
+    render_text(title, x, HEADER_Y, color(false), font, canvas)?;
+
+    // the default device first, then as many as there are number keys left
+    let rows = std::iter::once((None, String::from("default")))
+        .chain(
+            devices
+                .iter()
+                .map(|device| (Some(device), device.to_string())),
+        )
+        .take(9);
+    for (i, (device, name)) in rows.enumerate() {
+        let text = match picking {
+            true => format!("{} {name}", i + 1),
+            false => name,
+        };
+        let y = FIRST_ROW_Y + i as i32 * ROW_HEIGHT;
+        render_text(&text, x, y, color(device == current), font, canvas)?;
+    }
+    Ok(())
+}
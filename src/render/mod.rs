@@ -1,3 +1,15 @@
+pub mod clef;
+pub mod devices;
+#[allow(clippy::module_inception)]
 pub mod render;
+pub mod staff;
+pub mod statistics;
+pub mod text;
+pub mod tuner;
 
-pub use render::{render_staff, Sprites};
+pub use devices::render_devices;
+pub use render::{render_chord, render_staff, Sprites};
+pub use staff::Staff;
+pub use statistics::{render_statistics, StatisticsTexts};
+pub use text::{load_font, render_text};
+pub use tuner::{render_input_level, render_pitch_trace, render_tuner};
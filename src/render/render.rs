@@ -3,34 +3,138 @@ use std::path::Path;
 
 use sdl2::{image::LoadTexture, pixels::Color, render::RenderTarget};
 
-use crate::music::{Note, NoteName};
+use crate::music::{Key, Note};
+use crate::render::clef::Clef;
+use crate::render::staff::Staff;
 
 const HALF_SPACE: i32 = 20;
 const BOTTOM_LINE_Y: i32 = 249;
-const N_MAX_LEDGER_LINES: u8 = 3;
-const TREBLE_BOTTOM_NOTE: Note = Note { name: NoteName::E, alteration: 0, octave: 4 };
 
 const WHITE: Color = Color::RGB(255, 255, 255);
 
 const LEFT_X: i32 = 400;
 const RIGHT_X: i32 = 750;
 
+const KEY_SIGNATURE_X: i32 = 300;
+const KEY_SIGNATURE_SPACING: i32 = 40;
+/// from the end of the key signature to the first note
+const KEY_SIGNATURE_MARGIN: i32 = 100;
+const MIN_NOTE_SPACING: i32 = 180;
+/// where the last note of a melody goes, with the staff to itself
+const LAST_NOTE_X: i32 = 880;
+const MIN_MELODY_NOTE_SPACING: i32 = 80;
+/// the upper note of a second sits to the right of the lower one
+const SECOND_OFFSET: i32 = 70;
+/// between accidentals stacked in front of a chord
+const ACCIDENTAL_SPACING: i32 = 40;
+
+/// The notes one after the other, `None` leaving a note's place empty
 pub fn render_staff<T: RenderTarget>(
-    note1: Option<Note>,
-    note2: Option<Note>,
+    notes: &[Option<Note>],
+    staff: Staff,
+    key: Option<Key>,
     sprites: &Sprites,
     canvas: &mut sdl2::render::Canvas<T>,
 ) -> Result<(), String> {
     canvas.set_draw_color(WHITE);
     canvas.clear();
-    render_empty_staff(&sprites.staff, canvas)?;
+    render_empty_staff(sprites.staff(staff), canvas)?;
 
-    if let Some(note) = note1 {
-        render_note(note, LEFT_X, sprites, canvas)?;
+    let mut left_x = LEFT_X;
+    if let Some(key) = key {
+        let end_x = render_key_signature(key, staff, sprites, canvas)?;
+        left_x = left_x.max(end_x + KEY_SIGNATURE_MARGIN);
     }
+    // two notes are as far apart as they can be, longer melodies fill the staff
+    let spacing = match notes.len() {
+        0 | 1 => 0,
+        2 => (RIGHT_X - left_x).max(MIN_NOTE_SPACING),
+        n => ((LAST_NOTE_X - left_x) / (n as i32 - 1)).max(MIN_MELODY_NOTE_SPACING),
+    };
+    // no key is C major, where every accidental is written
+    let key = key.unwrap_or_default();
 
-    if let Some(note) = note2 {
-        render_note(note, RIGHT_X, sprites, canvas)?;
+    for (i, note) in notes.iter().enumerate() {
+        let Some(note) = *note else {
+            continue;
+        };
+        let earlier = notes[..i].iter().rev().flatten();
+        // read in the same hand as its neighbour on the grand staff
+        let neighbour = earlier
+            .clone()
+            .next()
+            .or(notes[i + 1..].iter().flatten().next());
+        let (clef, offset) = staff.place(note, neighbour.copied());
+        // an earlier note on the same line or space keeps its accidental
+        let earlier_note = earlier
+            .copied()
+            .find(|earlier| (earlier.name, earlier.octave) == (note.name, note.octave));
+        let accidental = key.accidental(note, earlier_note);
+        let bottom_line_y = BOTTOM_LINE_Y + offset;
+        render_note(
+            note,
+            accidental,
+            left_x + i as i32 * spacing,
+            clef,
+            bottom_line_y,
+            sprites,
+            canvas,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The notes stacked as a chord, from the bass up
+pub fn render_chord<T: RenderTarget>(
+    notes: &[Note],
+    staff: Staff,
+    key: Option<Key>,
+    sprites: &Sprites,
+    canvas: &mut sdl2::render::Canvas<T>,
+) -> Result<(), String> {
+    canvas.set_draw_color(WHITE);
+    canvas.clear();
+    render_empty_staff(sprites.staff(staff), canvas)?;
+
+    let mut x = LEFT_X;
+    if let Some(key) = key {
+        let end_x = render_key_signature(key, staff, sprites, canvas)?;
+        x = x.max(end_x + KEY_SIGNATURE_MARGIN);
+    }
+    // leaves room for the accidentals
+    let x = x + MIN_NOTE_SPACING / 2;
+    let key = key.unwrap_or_default();
+
+    // the clef and staff position of the previous note, and whether it was moved right
+    let mut previous: Option<(Clef, i32, bool)> = None;
+    let mut accidentals = Vec::new();
+    for &note in notes {
+        let other_note = notes.iter().find(|&&other| other != note).copied();
+        let (clef, offset) = staff.place(note, other_note);
+        let bottom_line_y = BOTTOM_LINE_Y + offset;
+
+        let staff_position = clef.staff_position(note);
+        let shifted = match previous {
+            Some((below_clef, below_position, below_shifted)) => {
+                below_clef == clef && staff_position - below_position == 1 && !below_shifted
+            }
+            None => false,
+        };
+        previous = Some((clef, staff_position, shifted));
+        let note_x = if shifted { x + SECOND_OFFSET } else { x };
+        render_note(note, None, note_x, clef, bottom_line_y, sprites, canvas)?;
+
+        if let Some(alteration) = key.accidental(note, None) {
+            let y = bottom_line_y - staff_position * HALF_SPACE;
+            accidentals.push((alteration, y));
+        }
+    }
+
+    // from the top down, each one further left so they don't overlap
+    for (i, (alteration, y)) in accidentals.into_iter().rev().enumerate() {
+        let x = x - i as i32 * ACCIDENTAL_SPACING;
+        render_alteration(alteration, x, y, sprites, canvas)?;
     }
 
     Ok(())
@@ -45,47 +149,72 @@ fn render_empty_staff<T: RenderTarget>(
     render_texture_at(staff, pos, canvas)
 }
 
+/// On every staff, returns where it ends
+fn render_key_signature<T: RenderTarget>(
+    key: Key,
+    staff: Staff,
+    sprites: &Sprites,
+    canvas: &mut sdl2::render::Canvas<T>,
+) -> Result<i32, String> {
+    let signature = key.signature();
+    let n_accidentals = signature.unsigned_abs() as usize;
+    let alteration = signature.signum();
+
+    for (clef, offset) in staff.clefs() {
+        let positions = clef.key_signature_positions(signature > 0);
+        for (i, staff_position) in positions.iter().take(n_accidentals).enumerate() {
+            let x = KEY_SIGNATURE_X + i as i32 * KEY_SIGNATURE_SPACING;
+            let y = BOTTOM_LINE_Y + offset - staff_position * HALF_SPACE;
+            // drawn as if in front of a note just to the right
+            render_alteration(alteration, x + 60, y, sprites, canvas)?;
+        }
+    }
+
+    Ok(KEY_SIGNATURE_X + n_accidentals as i32 * KEY_SIGNATURE_SPACING)
+}
+
 fn render_note<T: RenderTarget>(
     note: Note,
+    accidental: Option<i8>,
     x: i32,
+    clef: Clef,
+    bottom_line_y: i32,
     sprites: &Sprites,
     canvas: &mut sdl2::render::Canvas<T>,
 ) -> Result<(), String> {
-    let staff_position: i32 = Note::diatonic_distance(TREBLE_BOTTOM_NOTE, note).into();
-    let pos = Position { x, y: BOTTOM_LINE_Y - staff_position * HALF_SPACE };
+    let staff_position = clef.staff_position(note);
+    let pos = Position { x, y: bottom_line_y - staff_position * HALF_SPACE };
 
-    let ledgers = match staff_position {
-        i32::MIN..=-2 => staff_position / 2,
-        10..=i32::MAX => (staff_position - 8) / 2,
-        _ => 0,
-    };
+    let ledgers = clef.ledger_lines(note);
 
     match ledgers {
         i32::MIN..=-1 => {
             for i in 1..=ledgers.abs() {
-                render_ledger_line(pos.x, -i, &sprites.ledger_line, canvas)?;
+                render_ledger_line(pos.x, -i, bottom_line_y, &sprites.ledger_line, canvas)?;
             }
         }
         1..=i32::MAX => {
             for i in 1..=ledgers {
-                render_ledger_line(pos.x, i, &sprites.ledger_line, canvas)?;
+                render_ledger_line(pos.x, i, bottom_line_y, &sprites.ledger_line, canvas)?;
             }
         }
         0 => {}
     }
 
-    render_alteration(note.alteration, x, staff_position, sprites, canvas)?;
+    if let Some(alteration) = accidental {
+        render_alteration(alteration, x, pos.y, sprites, canvas)?;
+    }
     render_texture_at(&sprites.note_head, pos, canvas)
 }
 
 fn render_alteration<T: RenderTarget>(
     alteration: i8,
     x: i32,
-    staff_position: i32,
+    y: i32,
     sprites: &Sprites,
     canvas: &mut sdl2::render::Canvas<T>,
 ) -> Result<(), String> {
-    let pos = Position { x, y: BOTTOM_LINE_Y - staff_position * HALF_SPACE };
+    let pos = Position { x, y };
     match alteration {
         1 => render_texture_at(
             &sprites.sharp,
@@ -107,7 +236,11 @@ fn render_alteration<T: RenderTarget>(
             Position { x: pos.x - 90, y: pos.y - 50 },
             canvas,
         ),
-        0 => Ok(()),
+        0 => render_texture_at(
+            &sprites.natural,
+            Position { x: pos.x - 52, y: pos.y - 38 },
+            canvas,
+        ),
         _ => Err(String::from("no triple alterations allowed")),
     }
 }
@@ -115,6 +248,7 @@ fn render_alteration<T: RenderTarget>(
 fn render_ledger_line<T: RenderTarget>(
     x: i32,
     staff_position: i32,
+    bottom_line_y: i32,
     ledger_line: &sdl2::render::Texture,
     canvas: &mut sdl2::render::Canvas<T>,
 ) -> Result<(), String> {
@@ -123,7 +257,7 @@ fn render_ledger_line<T: RenderTarget>(
         Ordering::Greater => 8 + 2 * staff_position,
         Ordering::Less => 2 * staff_position,
     };
-    let pos = Position { x, y: BOTTOM_LINE_Y - staff_position * HALF_SPACE };
+    let pos = Position { x, y: bottom_line_y - staff_position * HALF_SPACE };
     let pos = Position { x: pos.x - 22, y: pos.y + 17 };
 
     render_texture_at(ledger_line, pos, canvas)
@@ -141,44 +275,60 @@ fn render_texture_at<T: RenderTarget>(
 }
 
 pub struct Sprites<'a> {
-    pub staff: sdl2::render::Texture<'a>,
+    pub staves: Vec<(Staff, sdl2::render::Texture<'a>)>,
     pub note_head: sdl2::render::Texture<'a>,
     pub ledger_line: sdl2::render::Texture<'a>,
     pub sharp: sdl2::render::Texture<'a>,
     pub flat: sdl2::render::Texture<'a>,
     pub double_sharp: sdl2::render::Texture<'a>,
     pub double_flat: sdl2::render::Texture<'a>,
+    pub natural: sdl2::render::Texture<'a>,
 }
 
 const PNG_DIR: &str = "src/assets/png";
-const TREBLE_STAFF_PATH: &str = "treble_staff.png";
 const NOTEHEAD_PATH: &str = "WholeNote.png";
 const LEDGER_LINE_PATH: &str = "ledger_line.png";
 const SHARP_PATH: &str = "Sharp.png";
 const FLAT_PATH: &str = "Flat.png";
 const DOUBLESHARP_PATH: &str = "DoubleSharp.png";
 const DOUBLEFLAT_PATH: &str = "DoubleFlat.png";
+const NATURAL_PATH: &str = "Natural.png";
 
 impl<'a> Sprites<'a> {
     pub fn init<T>(texture_creator: &'a sdl2::render::TextureCreator<T>) -> Result<Self, String> {
         let png_dir = Path::new(PNG_DIR);
-        let treble_staff = texture_creator.load_texture(&png_dir.join(TREBLE_STAFF_PATH))?;
-        let note_head = texture_creator.load_texture(&png_dir.join(NOTEHEAD_PATH))?;
-        let ledger_line = texture_creator.load_texture(&png_dir.join(LEDGER_LINE_PATH))?;
-        let sharp = texture_creator.load_texture(&png_dir.join(SHARP_PATH))?;
-        let flat = texture_creator.load_texture(&png_dir.join(FLAT_PATH))?;
-        let double_sharp = texture_creator.load_texture(&png_dir.join(DOUBLESHARP_PATH))?;
-        let double_flat = texture_creator.load_texture(&png_dir.join(DOUBLEFLAT_PATH))?;
-
-        Ok(Self {
-            staff: treble_staff,
-            note_head,
-            ledger_line,
-            sharp,
-            flat,
-            double_sharp,
-            double_flat,
-        })
+        let mut staves = Vec::new();
+        for staff in Staff::all() {
+            let sprite = texture_creator.load_texture(png_dir.join(staff.sprite()))?;
+            staves.push((staff, sprite));
+        }
+        let note_head = texture_creator.load_texture(png_dir.join(NOTEHEAD_PATH))?;
+        let ledger_line = texture_creator.load_texture(png_dir.join(LEDGER_LINE_PATH))?;
+        let sharp = texture_creator.load_texture(png_dir.join(SHARP_PATH))?;
+        let flat = texture_creator.load_texture(png_dir.join(FLAT_PATH))?;
+        let double_sharp = texture_creator.load_texture(png_dir.join(DOUBLESHARP_PATH))?;
+        let double_flat = texture_creator.load_texture(png_dir.join(DOUBLEFLAT_PATH))?;
+        let natural = texture_creator.load_texture(png_dir.join(NATURAL_PATH))?;
+
+        Ok(
+            Self {
+                staves,
+                note_head,
+                ledger_line,
+                sharp,
+                flat,
+                double_sharp,
+                double_flat,
+                natural,
+            },
+        )
+    }
+}
+
+impl Sprites<'_> {
+    pub fn staff(&self, staff: Staff) -> &sdl2::render::Texture<'_> {
+        let (_, sprite) = self.staves.iter().find(|(s, _)| *s == staff).unwrap();
+        sprite
     }
 }
 
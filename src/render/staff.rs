@@ -0,0 +1,158 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::music::{Note, NoteRange};
+use crate::render::clef::Clef;
+
+/// Past this many ledger lines, a range is read on the grand staff instead
+const N_MAX_LEDGER_LINES: i32 = 3;
+
+/// Height of a single staff sprite, in pixels
+const STAFF_HEIGHT: u32 = 400;
+/// How far below the treble staff the bass staff of the grand staff is drawn, in pixels.
+/// Matches the 8 staff spaces between the staves of `assets/svg/from_lilypond/grand_staff.svg`
+const GRAND_STAFF_GAP: u32 = 320;
+
+/// What the notes are drawn on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Staff {
+    Single(Clef),
+    /// treble and bass staves joined by a brace, for pianists
+    Grand,
+}
+
+impl Default for Staff {
+    fn default() -> Self {
+        Staff::Single(Clef::default())
+    }
+}
+
+impl Staff {
+    pub fn all() -> [Staff; 6] {
+        let [treble, bass, sub_octave_treble, alto, tenor] = Clef::all().map(Staff::Single);
+        [treble, bass, sub_octave_treble, alto, tenor, Staff::Grand]
+    }
+
+    /// The clef with the fewest ledger lines, or the grand staff if even that one needs too many
+    pub fn for_range(range: &NoteRange) -> Staff {
+        let clef = Clef::for_range(range);
+        match clef.worst_ledger_lines(range) > N_MAX_LEDGER_LINES {
+            true => Staff::Grand,
+            false => Staff::Single(clef),
+        }
+    }
+
+    /// What the instrument or voice of a range preset reads, see `NoteRange::parse`
+    pub fn for_instrument(preset: &str) -> Option<Staff> {
+        match preset {
+            "piano" => Some(Staff::Grand),
+            _ => Clef::for_instrument(preset).map(Staff::Single),
+        }
+    }
+
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            Staff::Single(clef) => clef.staff_sprite(),
+            Staff::Grand => "grand_staff.png",
+        }
+    }
+
+    /// Of the sprite, which is as wide as the window
+    pub fn height(&self) -> u32 {
+        match self {
+            Staff::Single(_) => STAFF_HEIGHT,
+            Staff::Grand => STAFF_HEIGHT + GRAND_STAFF_GAP,
+        }
+    }
+
+    /// Every clef, and how far down its staff is drawn
+    pub fn clefs(&self) -> Vec<(Clef, i32)> {
+        match self {
+            Staff::Single(clef) => vec![(*clef, 0)],
+            Staff::Grand => vec![(Clef::Treble, 0), (Clef::Bass, GRAND_STAFF_GAP as i32)],
+        }
+    }
+
+    /// The clef the note is read in, and how far down its staff is drawn.
+    ///
+    /// On the grand staff, a note goes on whichever staff needs fewer ledger lines. Middle C
+    /// needs one on either, so it goes with the other note, to be read in the same hand
+    pub fn place(&self, note: Note, other_note: Option<Note>) -> (Clef, i32) {
+        match self {
+            Staff::Single(clef) => (*clef, 0),
+            Staff::Grand => {
+                let closest_staff = |note: Note| {
+                    let treble = Clef::Treble.ledger_lines(note).abs();
+                    let bass = Clef::Bass.ledger_lines(note).abs();
+                    match treble.cmp(&bass) {
+                        Ordering::Less => Some(Clef::Treble),
+                        Ordering::Greater => Some(Clef::Bass),
+                        Ordering::Equal => None,
+                    }
+                };
+
+                let clef = closest_staff(note)
+                    .or_else(|| other_note.and_then(closest_staff))
+                    .unwrap_or(Clef::Treble);
+                match clef {
+                    Clef::Bass => (clef, GRAND_STAFF_GAP as i32),
+                    _ => (clef, 0),
+                }
+            }
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        Staff::all()
+            .into_iter()
+            .find(|staff| staff.to_string() == string)
+            .ok_or(format!(
+                "invalid clef: {string} (expected auto, {})",
+                Staff::all().map(|s| s.to_string()).join(", ")
+            ))
+    }
+}
+
+impl fmt::Display for Staff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Staff::Single(clef) => write!(f, "{}", clef),
+            Staff::Grand => write!(f, "grand"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(string: &str) -> Note {
+        Note::parse_from_string(string).unwrap()
+    }
+
+    #[test]
+    fn notes_go_on_the_closest_staff() {
+        let grand = Staff::Grand;
+
+        assert_eq!(grand.place(note("D4"), None), (Clef::Treble, 0));
+        assert_eq!(grand.place(note("B3"), None).0, Clef::Bass);
+        assert_eq!(grand.place(note("G5"), Some(note("F2"))).0, Clef::Treble);
+        // middle C goes with the other note
+        assert_eq!(grand.place(note("C4"), Some(note("A3"))).0, Clef::Bass);
+        assert_eq!(grand.place(note("C4"), Some(note("E4"))).0, Clef::Treble);
+        assert_eq!(grand.place(note("C4"), None).0, Clef::Treble);
+    }
+
+    #[test]
+    fn wide_ranges_get_the_grand_staff() {
+        assert_eq!(Staff::for_instrument("piano"), Some(Staff::Grand));
+        assert_eq!(
+            Staff::for_range(&NoteRange::parse("C2-C6").unwrap()),
+            Staff::Grand
+        );
+        assert_eq!(
+            Staff::for_range(&NoteRange::bass_voice()),
+            Staff::Single(Clef::Bass)
+        );
+    }
+}
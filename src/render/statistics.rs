@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    ttf::Font,
+    video::{Window, WindowContext},
+};
+
+use crate::render::text::text_texture;
+use crate::stats::{interval_class_repr, Statistics, N_INTERVAL_CLASSES};
+
+const WHITE: Color = Color::RGB(255, 255, 255);
+const BLACK: Color = Color::RGB(0, 0, 0);
+const GREY: Color = Color::RGB(220, 220, 220);
+const GREEN: Color = Color::RGB(60, 170, 80);
+const RED: Color = Color::RGB(210, 60, 50);
+const BLUE: Color = Color::RGB(70, 110, 200);
+
+const MARGIN: i32 = 20;
+const HEADER_Y: i32 = 12;
+const FIRST_ROW_Y: i32 = 42;
+const ROW_HEIGHT: i32 = 22;
+const BAR_HEIGHT: u32 = 14;
+
+const NAME_X: i32 = MARGIN;
+const ATTEMPTS_X: i32 = 70;
+const ACCURACY_BAR_X: i32 = 110;
+const ACCURACY_BAR_WIDTH: u32 = 140;
+const ACCURACY_X: i32 = 258;
+const CENTS_X: i32 = 310;
+const RESPONSE_BAR_X: i32 = 380;
+const RESPONSE_BAR_WIDTH: u32 = 120;
+const RESPONSE_X: i32 = 508;
+/// response times are drawn relative to this
+const SLOWEST_RESPONSE: Duration = Duration::from_millis(1500);
+
+const CONFUSION_X: i32 = 640;
+const CONFUSION_Y: i32 = 60;
+const CELL_SIZE: i32 = 26;
+
+/// The text of the statistics scene, rendered again only when the statistics change rather than
+/// on every frame
+#[derive(Default)]
+pub struct StatisticsTexts<'a> {
+    /// what the textures were rendered from
+    statistics: Option<Statistics>,
+    texts: Vec<(Texture<'a>, Rect)>,
+}
+
+impl<'a> StatisticsTexts<'a> {
+    fn update(
+        &mut self,
+        statistics: &Statistics,
+        max_rows: usize,
+        font: &Font,
+        texture_creator: &'a TextureCreator<WindowContext>,
+    ) -> Result<(), String> {
+        if self.statistics.as_ref() == Some(statistics) {
+            return Ok(());
+        }
+
+        let labels = match statistics.is_empty() {
+            true => vec![(
+                String::from("No exercise recorded yet, press Space to start"),
+                MARGIN,
+                HEADER_Y,
+            )],
+            false => {
+                let mut labels = table_labels(statistics, max_rows);
+                labels.extend(confusion_labels());
+                labels
+            }
+        };
+        self.texts = labels
+            .into_iter()
+            .filter(|(text, _, _)| !text.is_empty())
+            .map(|(text, x, y)| {
+                let texture = text_texture(&text, BLACK, font, texture_creator)?;
+                let query = texture.query();
+                Ok((texture, Rect::new(x, y, query.width, query.height)))
+            })
+            .collect::<Result<_, String>>()?;
+        self.statistics = Some(statistics.clone());
+        Ok(())
+    }
+}
+
+/// Per-interval accuracy, cent deviation and response time on the left,
+/// what was sung against what was asked on the right
+pub fn render_statistics<'a>(
+    statistics: &Statistics,
+    texts: &mut StatisticsTexts<'a>,
+    font: &Font,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(WHITE);
+    canvas.clear();
+
+    // the prompt goes below
+    let max_rows =
+        ((canvas.viewport().height() as i32 - FIRST_ROW_Y) / ROW_HEIGHT - 2).max(0) as usize;
+    texts.update(statistics, max_rows, font, texture_creator)?;
+    if !statistics.is_empty() {
+        render_bars(statistics, max_rows, canvas)?;
+        render_confusion(statistics, canvas)?;
+    }
+    for (texture, rect) in &texts.texts {
+        canvas.copy(texture, None, Some(*rect))?;
+    }
+    Ok(())
+}
+
+/// Where every piece of text of the table goes
+fn table_labels(statistics: &Statistics, max_rows: usize) -> Vec<(String, i32, i32)> {
+    let mut labels = vec![
+        (String::from("n"), ATTEMPTS_X, HEADER_Y),
+        (String::from("accuracy"), ACCURACY_BAR_X, HEADER_Y),
+        (String::from("cents"), CENTS_X, HEADER_Y),
+        (String::from("median response"), RESPONSE_BAR_X, HEADER_Y),
+    ];
+
+    for (i, row) in statistics.intervals.iter().take(max_rows).enumerate() {
+        let y = FIRST_ROW_Y + i as i32 * ROW_HEIGHT;
+        labels.push((row.interval.short_repr(), NAME_X, y));
+        labels.push((row.attempts.to_string(), ATTEMPTS_X, y));
+        labels.push((format!("{:.0}%", 100.0 * row.accuracy()), ACCURACY_X, y));
+        if let Some(cents) = row.average_cent_deviation {
+            labels.push((format!("{cents:+.0}"), CENTS_X, y));
+        }
+        if let Some(response_time) = row.median_response_time {
+            labels.push((format!("{}ms", response_time.as_millis()), RESPONSE_X, y));
+        }
+    }
+    labels
+}
+
+fn render_bars(
+    statistics: &Statistics,
+    max_rows: usize,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    for (i, row) in statistics.intervals.iter().take(max_rows).enumerate() {
+        let y = FIRST_ROW_Y + i as i32 * ROW_HEIGHT;
+        let bar_y = y + (ROW_HEIGHT - BAR_HEIGHT as i32) / 2 - 2;
+
+        render_bar(
+            ACCURACY_BAR_X,
+            bar_y,
+            ACCURACY_BAR_WIDTH,
+            row.accuracy(),
+            GREEN,
+            canvas,
+        )?;
+        if let Some(response_time) = row.median_response_time {
+            let ratio = response_time.as_secs_f64() / SLOWEST_RESPONSE.as_secs_f64();
+            render_bar(
+                RESPONSE_BAR_X,
+                bar_y,
+                RESPONSE_BAR_WIDTH,
+                ratio,
+                BLUE,
+                canvas,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn render_bar(
+    x: i32,
+    y: i32,
+    width: u32,
+    ratio: f64,
+    color: Color,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(GREY);
+    canvas.fill_rect(Rect::new(x, y, width, BAR_HEIGHT))?;
+
+    let filled = (ratio.clamp(0.0, 1.0) * width as f64) as u32;
+    if filled > 0 {
+        canvas.set_draw_color(color);
+        canvas.fill_rect(Rect::new(x, y, filled, BAR_HEIGHT))?;
+    }
+
+    Ok(())
+}
+
+/// The labels of the confusion grid
+fn confusion_labels() -> Vec<(String, i32, i32)> {
+    let mut labels = vec![(String::from("asked \\ sung"), CONFUSION_X - 40, HEADER_Y)];
+    for class in 0..N_INTERVAL_CLASSES {
+        let offset = class as i32 * CELL_SIZE;
+        let label = interval_class_repr(class);
+        labels.push((
+            label.to_string(),
+            CONFUSION_X + offset + 2,
+            CONFUSION_Y - 22,
+        ));
+        labels.push((
+            label.to_string(),
+            CONFUSION_X - 30,
+            CONFUSION_Y + offset + 3,
+        ));
+    }
+    labels
+}
+
+/// One row per asked interval class, one column per sung interval class,
+/// shaded by the share of the row's attempts
+fn render_confusion(statistics: &Statistics, canvas: &mut Canvas<Window>) -> Result<(), String> {
+    for (asked, row) in statistics.confusion.iter().enumerate() {
+        let total: u32 = row.iter().sum();
+        for (sung, &count) in row.iter().enumerate() {
+            let cell = Rect::new(
+                CONFUSION_X + sung as i32 * CELL_SIZE,
+                CONFUSION_Y + asked as i32 * CELL_SIZE,
+                CELL_SIZE as u32,
+                CELL_SIZE as u32,
+            );
+
+            let share = match total {
+                0 => 0.0,
+                _ => count as f64 / total as f64,
+            };
+            let color = match asked == sung {
+                true => GREEN,
+                false => RED,
+            };
+            canvas.set_draw_color(blend(WHITE, color, share));
+            canvas.fill_rect(cell)?;
+            canvas.set_draw_color(GREY);
+            canvas.draw_rect(cell)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn blend(from: Color, to: Color, ratio: f64) -> Color {
+    let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * ratio.clamp(0.0, 1.0)) as u8;
+
+    Color::RGB(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+    )
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    ttf::{Font, Sdl2TtfContext},
+    video::{Window, WindowContext},
+};
+
+const FONT_PATH: &str = "src/assets/fonts/DejaVuSans.ttf";
+
+pub fn load_font(
+    ttf_context: &Sdl2TtfContext,
+    point_size: u16,
+) -> Result<Font<'_, 'static>, String> {
+    ttf_context.load_font(Path::new(FONT_PATH), point_size)
+}
+
+/// Draws a single line of text with its top left corner at (x, y)
+pub fn render_text(
+    text: &str,
+    x: i32,
+    y: i32,
+    color: Color,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    let texture_creator = canvas.texture_creator();
+    let texture = text_texture(text, color, font, &texture_creator)?;
+    let query = texture.query();
+    canvas.copy(
+        &texture,
+        None,
+        Some(Rect::new(x, y, query.width, query.height)),
+    )
+}
+
+/// A single line of text, to be drawn over and over without rendering it again
+pub fn text_texture<'a>(
+    text: &str,
+    color: Color,
+    font: &Font,
+    texture_creator: &'a TextureCreator<WindowContext>,
+) -> Result<Texture<'a>, String> {
+    let surface = font
+        .render(text)
+        .blended(color)
+        .map_err(|e| e.to_string())?;
+    texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,226 @@
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, ttf::Font, video::Window};
+
+use crate::audio::detection::{CentDeviation, HeardNote, PitchReading};
+use crate::music::{Note, SimpleNote};
+use crate::render::text::render_text;
+
+const WHITE: Color = Color::RGB(255, 255, 255);
+const BLACK: Color = Color::RGB(0, 0, 0);
+const GREY: Color = Color::RGB(220, 220, 220);
+const LIGHT_GREEN: Color = Color::RGB(215, 240, 220);
+const GREEN: Color = Color::RGB(60, 170, 80);
+const RED: Color = Color::RGB(210, 60, 50);
+
+const NOTE_Y: i32 = 40;
+const METER_Y: i32 = 190;
+const METER_HEIGHT: u32 = 80;
+/// the meter goes from -50 to +50 cents
+const PIXELS_PER_CENT: i32 = 8;
+const TICK_SPACING: i32 = 10;
+const NEEDLE_WIDTH: u32 = 4;
+const DETAILS_Y: i32 = 300;
+
+const TRACE_X: i32 = 700;
+const TRACE_Y: i32 = 20;
+const TRACE_WIDTH: u32 = 280;
+const TRACE_HEIGHT: u32 = 80;
+/// cents off the target at the top and bottom of the trace
+const TRACE_RANGE: i32 = 100;
+const DOT_SIZE: u32 = 3;
+
+const LEVEL_Y: i32 = 150;
+const LEVEL_HEIGHT: u32 = 60;
+/// powers from 10^-3 to 10^3 across the width of the meter
+const LEVEL_DECADES: (f32, f32) = (-3.0, 3.0);
+const LEVEL_MARGIN: i32 = 100;
+
+/// The closest note in large, and a needle showing how many cents off it is
+pub fn render_tuner(
+    tuning: Option<(Note, HeardNote)>,
+    in_tune: CentDeviation,
+    large_font: &Font,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(WHITE);
+    canvas.clear();
+    let center_x = canvas.viewport().width() as i32 / 2;
+
+    // in tune around the middle
+    let in_tune_width = in_tune as i32 * PIXELS_PER_CENT;
+    canvas.set_draw_color(LIGHT_GREEN);
+    canvas.fill_rect(Rect::new(
+        center_x - in_tune_width,
+        METER_Y,
+        2 * in_tune_width as u32,
+        METER_HEIGHT,
+    ))?;
+    for cents in (-50..=50).step_by(TICK_SPACING as usize) {
+        let x = center_x + cents * PIXELS_PER_CENT;
+        let height = match cents {
+            0 => METER_HEIGHT,
+            _ => METER_HEIGHT / 3,
+        };
+        canvas.set_draw_color(BLACK);
+        canvas.fill_rect(Rect::new(x - 1, METER_Y, 2, height))?;
+    }
+    render_centered(
+        "flat",
+        center_x - 50 * PIXELS_PER_CENT,
+        METER_Y + 90,
+        font,
+        canvas,
+    )?;
+    render_centered(
+        "sharp",
+        center_x + 50 * PIXELS_PER_CENT,
+        METER_Y + 90,
+        font,
+        canvas,
+    )?;
+
+    let Some((note, heard)) = tuning else {
+        return render_centered("Play or sing a note", center_x, NOTE_Y, font, canvas);
+    };
+
+    render_centered(&note.to_string(), center_x, NOTE_Y, large_font, canvas)?;
+
+    let color = match heard.cent_deviation.abs() < in_tune {
+        true => GREEN,
+        false => RED,
+    };
+    let x = center_x + heard.cent_deviation as i32 * PIXELS_PER_CENT;
+    canvas.set_draw_color(color);
+    canvas.fill_rect(Rect::new(
+        x - NEEDLE_WIDTH as i32 / 2,
+        METER_Y - 10,
+        NEEDLE_WIDTH,
+        METER_HEIGHT + 20,
+    ))?;
+
+    let details = format!(
+        "{:+} cents, {:.1} Hz",
+        heard.cent_deviation, heard.frequency
+    );
+    render_centered(&details, center_x, DETAILS_Y, font, canvas)
+}
+
+/// What was heard while listening for every note, one after the other, the line in the middle
+/// being the expected note and a dot being drawn for every reading
+pub fn render_pitch_trace(
+    trace: &[(SimpleNote, &[PitchReading])],
+    in_tune: CentDeviation,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    if trace.is_empty() {
+        return Ok(());
+    }
+
+    let frame = Rect::new(TRACE_X, TRACE_Y, TRACE_WIDTH, TRACE_HEIGHT);
+    let center_y = frame.center().y();
+    let y_of = |cents: i32| {
+        center_y - cents.clamp(-TRACE_RANGE, TRACE_RANGE) * TRACE_HEIGHT as i32 / 2 / TRACE_RANGE
+    };
+
+    canvas.set_draw_color(WHITE);
+    canvas.fill_rect(frame)?;
+    let in_tune = in_tune as i32;
+    canvas.set_draw_color(LIGHT_GREEN);
+    canvas.fill_rect(Rect::new(
+        TRACE_X,
+        y_of(in_tune),
+        TRACE_WIDTH,
+        (y_of(-in_tune) - y_of(in_tune)) as u32,
+    ))?;
+    canvas.set_draw_color(GREY);
+    canvas.draw_line((TRACE_X, center_y), (frame.right(), center_y))?;
+
+    let segment_width = TRACE_WIDTH as i32 / trace.len() as i32;
+    for (i, (expected, readings)) in trace.iter().enumerate() {
+        let left_x = TRACE_X + i as i32 * segment_width;
+        if i > 0 {
+            canvas.set_draw_color(GREY);
+            canvas.draw_line((left_x, TRACE_Y), (left_x, frame.bottom()))?;
+        }
+
+        // spread over the segment, however long listening took
+        let Some(last) = readings.last() else {
+            continue;
+        };
+        let duration = last.at.as_secs_f64().max(f64::EPSILON);
+        for reading in readings.iter() {
+            let Some(heard) = reading.heard else {
+                continue;
+            };
+            let cents = heard.cents_off(*expected);
+            let x =
+                left_x + (reading.at.as_secs_f64() / duration * (segment_width - 4) as f64) as i32;
+            canvas.set_draw_color(match cents.abs() < in_tune {
+                true => GREEN,
+                false => RED,
+            });
+            canvas.fill_rect(Rect::new(x, y_of(cents) - 1, DOT_SIZE, DOT_SIZE))?;
+        }
+    }
+
+    canvas.set_draw_color(GREY);
+    canvas.draw_rect(frame)
+}
+
+/// How loud the input is on a log scale, against the power threshold
+pub fn render_input_level(
+    power: Option<f32>,
+    power_threshold: f32,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(WHITE);
+    canvas.clear();
+
+    let width = canvas.viewport().width() as i32 - 2 * LEVEL_MARGIN;
+    let x_of = |power: f32| {
+        let (low, high) = LEVEL_DECADES;
+        let ratio = (power.max(f32::MIN_POSITIVE).log10() - low) / (high - low);
+        LEVEL_MARGIN + (ratio.clamp(0.0, 1.0) * width as f32) as i32
+    };
+
+    canvas.set_draw_color(GREY);
+    canvas.fill_rect(Rect::new(LEVEL_MARGIN, LEVEL_Y, width as u32, LEVEL_HEIGHT))?;
+    if let Some(power) = power {
+        canvas.set_draw_color(match power < power_threshold {
+            true => RED,
+            false => GREEN,
+        });
+        let filled = (x_of(power) - LEVEL_MARGIN) as u32;
+        if filled > 0 {
+            canvas.fill_rect(Rect::new(LEVEL_MARGIN, LEVEL_Y, filled, LEVEL_HEIGHT))?;
+        }
+    }
+
+    let threshold_x = x_of(power_threshold);
+    canvas.set_draw_color(BLACK);
+    canvas.fill_rect(Rect::new(
+        threshold_x - 1,
+        LEVEL_Y - 10,
+        2,
+        LEVEL_HEIGHT + 20,
+    ))?;
+    render_centered(
+        "current threshold",
+        threshold_x,
+        LEVEL_Y + LEVEL_HEIGHT as i32 + 15,
+        font,
+        canvas,
+    )
+}
+
+fn render_centered(
+    text: &str,
+    center_x: i32,
+    y: i32,
+    font: &Font,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    let (width, _) = font.size_of(text).map_err(|e| e.to_string())?;
+    render_text(text, center_x - width as i32 / 2, y, BLACK, font, canvas)
+}
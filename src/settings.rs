@@ -0,0 +1,788 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::audio::detection::DetectionSettings;
+use crate::audio::devices::{DeviceChoice, DeviceChoices};
+use crate::audio::listen::OctaveMatching;
+use crate::audio::synth::Timbre;
+use crate::instrument::Instrument;
+use crate::music::{
+    ChordQuality, ChordTone, Direction, IntervalPool, Key, Melody, NamingPolicy, NoteRange,
+    Presentation,
+};
+use crate::render::Staff;
+
+/// Session settings, chosen once on the command line
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub command: Command,
+    pub drill: Drill,
+    /// as written for the instrument
+    pub range: NoteRange,
+    /// concert pitch when `None`
+    pub instrument: Option<Instrument>,
+    /// picked from the range when `None`
+    pub staff: Option<Staff>,
+    /// no key signature and random spellings when `None`
+    pub key: Option<Key>,
+    pub spelling: SpellingMode,
+    pub direction: DirectionMode,
+    pub presentation: PresentationMode,
+    pub intervals: IntervalPool,
+    pub chords: ChordSet,
+    pub chord_answer: ChordAnswerMode,
+    pub melody: MelodyOptions,
+    /// scale degrees outside the key too, raised or lowered
+    pub chromatic: bool,
+    pub octaves: OctaveMatching,
+    pub detection: DetectionSettings,
+    /// thresholds from the command line win over a saved calibration
+    pub thresholds_given: bool,
+    pub history: HistoryLocation,
+    pub audio: AudioBackendKind,
+    /// from `--input` and `--output`. The trainer fills in the missing ones with those picked in
+    /// the menu last time, but never saves these, only what's picked in the menu
+    pub devices: DeviceChoices,
+    pub timbre: Timbre,
+}
+
+/// What to do, given as the first argument
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Command {
+    /// the app itself
+    #[default]
+    Train,
+    /// write exercises to WAV files, without any window or sound device
+    Render(RenderOptions),
+    /// print the sound devices to pick from
+    Devices,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub count: usize,
+    pub out: PathBuf,
+    pub note_length: Duration,
+    /// silence between the two notes of a melodic exercise
+    pub spacing: Duration,
+    pub sample_rate: u32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            count: 20,
+            out: PathBuf::from("drills"),
+            note_length: Duration::from_millis(1000),
+            spacing: Duration::from_millis(1000),
+            sample_rate: 44_100,
+        }
+    }
+}
+
+/// Where the practice history is kept
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum HistoryLocation {
+    /// in the user's data directory
+    #[default]
+    Default,
+    At(PathBuf),
+    Disabled,
+}
+
+/// Where sound goes to and comes from
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum AudioBackendKind {
+    /// the output and input devices of the system
+    #[default]
+    Device,
+    /// no sound at all, nothing is ever heard
+    Null,
+    /// the synth is heard instead of a microphone
+    Loopback,
+}
+
+/// What is being practiced
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Drill {
+    /// sing the reference, then the interval from it
+    #[default]
+    Intervals,
+    /// hear a cadence, then sing a note and find out its scale degree
+    ScaleDegrees,
+    /// hear a chord and name its quality
+    Chords,
+    /// hear a short tune and sing it back
+    Melodies,
+}
+
+/// The tunes of the melody drill
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MelodyOptions {
+    /// number of notes
+    pub length: usize,
+    pub leaps: Leaps,
+}
+
+/// How far a melody goes from one note to the next
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Leaps {
+    #[default]
+    Steps,
+    Thirds,
+    Fifths,
+    Octaves,
+}
+
+/// The chord qualities to draw from
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ChordSet {
+    #[default]
+    Triads,
+    Sevenths,
+    All,
+}
+
+/// How the quality of a chord is answered
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ChordAnswerMode {
+    /// picked with the number keys
+    #[default]
+    Keyboard,
+    /// by singing one of the notes
+    Sing(ChordTone),
+}
+
+/// Which way the mystery note goes from the reference
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DirectionMode {
+    #[default]
+    Ascending,
+    Descending,
+    Mixed,
+}
+
+/// Whether the notes are played one after the other, together, or either
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PresentationMode {
+    #[default]
+    Melodic,
+    Harmonic,
+    Mixed,
+}
+
+/// How the reference notes are spelled
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SpellingMode {
+    /// as in the key if there's one, at random otherwise
+    #[default]
+    Auto,
+    Sharps,
+    Flats,
+    Random,
+    Key,
+    FewestAccidentals,
+}
+
+impl Default for MelodyOptions {
+    fn default() -> Self {
+        MelodyOptions { length: 4, leaps: Leaps::default() }
+    }
+}
+
+impl Settings {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        let mut args = args.skip(1).peekable();
+        let mut range_given = false;
+        // presets are at concert pitch, ranges of two notes are as written
+        let mut range_sounding = false;
+        // the clef of the last --range preset, unless a --clef other than auto was given
+        let mut range_staff = None;
+        let mut clef_given = false;
+        // added to whatever --intervals ends up being, wherever it comes
+        let mut compound = false;
+
+        if args.next_if(|arg| arg == "render").is_some() {
+            settings.command = Command::Render(RenderOptions::default());
+        } else if args.next_if(|arg| arg == "devices").is_some() {
+            settings.command = Command::Devices;
+        }
+
+        while let Some(arg) = args.next() {
+            if let Command::Render(options) = &mut settings.command {
+                if options.parse_arg(&arg, &mut args)? {
+                    continue;
+                }
+            }
+
+            match arg.as_str() {
+                "--drill" => {
+                    let value = args.next().ok_or("--drill expects a value")?;
+                    settings.drill = Drill::parse(&value)?;
+                }
+                "--chords" => {
+                    let value = args.next().ok_or("--chords expects a value")?;
+                    settings.chords = ChordSet::parse(&value)?;
+                }
+                "--chord-answer" => {
+                    let value = args.next().ok_or("--chord-answer expects a value")?;
+                    settings.chord_answer = match value.as_str() {
+                        "keyboard" => ChordAnswerMode::Keyboard,
+                        _ => ChordAnswerMode::Sing(ChordTone::parse(&value)?),
+                    };
+                }
+                "--melody-length" => {
+                    let value = args.next().ok_or("--melody-length expects a value")?;
+                    settings.melody.length = match value.parse() {
+                        Ok(length @ 2..=8) => length,
+                        _ => return Err(format!("--melody-length expects 2 to 8, got {value}")),
+                    };
+                }
+                "--leaps" => {
+                    let value = args.next().ok_or("--leaps expects a value")?;
+                    settings.melody.leaps = Leaps::parse(&value)?;
+                }
+                "--octaves" => {
+                    let value = args.next().ok_or("--octaves expects a value")?;
+                    settings.octaves = OctaveMatching::parse(&value)?;
+                }
+                "--power-threshold" => {
+                    let value = args.next().ok_or("--power-threshold expects a value")?;
+                    settings.detection.power_threshold = match value.parse() {
+                        Ok(power) if power >= 0.0 => power,
+                        _ => {
+                            return Err(format!("--power-threshold expects a number, got {value}"))
+                        }
+                    };
+                    settings.thresholds_given = true;
+                }
+                "--clarity-threshold" => {
+                    let value = args.next().ok_or("--clarity-threshold expects a value")?;
+                    settings.detection.clarity_threshold = match value.parse() {
+                        Ok(clarity) if (0.0..=1.0).contains(&clarity) => clarity,
+                        _ => {
+                            return Err(format!("--clarity-threshold expects 0 to 1, got {value}"))
+                        }
+                    };
+                    settings.thresholds_given = true;
+                }
+                "--cent-tolerance" => {
+                    let value = args.next().ok_or("--cent-tolerance expects a value")?;
+                    settings.detection.cent_deviation_threshold = match value.parse() {
+                        Ok(cents @ 1..=50) => cents,
+                        _ => return Err(format!("--cent-tolerance expects 1 to 50, got {value}")),
+                    };
+                }
+                "--buffer-size" => {
+                    let value = args.next().ok_or("--buffer-size expects a value")?;
+                    settings.detection.buffer_size = match value.parse::<usize>() {
+                        Ok(size @ 256..=8192) if size.is_power_of_two() => size,
+                        _ => {
+                            return Err(format!(
+                                "--buffer-size expects a power of two from 256 to 8192, got {value}"
+                            ))
+                        }
+                    };
+                }
+                "--listen-ms" => {
+                    let value = args.next().ok_or("--listen-ms expects a value")?;
+                    settings.detection.window = match value.parse() {
+                        Ok(ms @ 200..=10_000) => Duration::from_millis(ms),
+                        _ => return Err(format!("--listen-ms expects 200 to 10000, got {value}")),
+                    };
+                }
+                "--range" => {
+                    let value = args.next().ok_or("--range expects a value")?;
+                    settings.range = NoteRange::parse(&value)?;
+                    range_given = true;
+                    range_sounding = NoteRange::preset(&value).is_some();
+                    range_staff = Staff::for_instrument(&value);
+                }
+                "--instrument" => {
+                    let value = args.next().ok_or("--instrument expects a value")?;
+                    settings.instrument = match value.as_str() {
+                        "concert" => None,
+                        _ => Some(Instrument::parse(&value)?),
+                    };
+                }
+                "--clef" => {
+                    let value = args.next().ok_or("--clef expects a value")?;
+                    settings.staff = match value.as_str() {
+                        "auto" => None,
+                        _ => Some(Staff::parse(&value)?),
+                    };
+                    clef_given = settings.staff.is_some();
+                }
+                "--key" => {
+                    let value = args.next().ok_or("--key expects a value")?;
+                    settings.key = match value.as_str() {
+                        "none" => None,
+                        _ => Some(Key::parse(&value)?),
+                    };
+                }
+                "--spelling" => {
+                    let value = args.next().ok_or("--spelling expects a value")?;
+                    settings.spelling = SpellingMode::parse(&value)?;
+                }
+                "--direction" => {
+                    let value = args.next().ok_or("--direction expects a value")?;
+                    settings.direction = DirectionMode::parse(&value)?;
+                }
+                "--presentation" => {
+                    let value = args.next().ok_or("--presentation expects a value")?;
+                    match value.as_str() {
+                        // shorthands for a melodic presentation in a given direction
+                        "melodic-up" => {
+                            settings.presentation = PresentationMode::Melodic;
+                            settings.direction = DirectionMode::Ascending;
+                        }
+                        "melodic-down" => {
+                            settings.presentation = PresentationMode::Melodic;
+                            settings.direction = DirectionMode::Descending;
+                        }
+                        _ => settings.presentation = PresentationMode::parse(&value)?,
+                    }
+                }
+                "--intervals" => {
+                    let value = args.next().ok_or("--intervals expects a value")?;
+                    settings.intervals = IntervalPool::parse(&value)?;
+                }
+                "--compound" => compound = true,
+                "--chromatic" => settings.chromatic = true,
+                "--history" => {
+                    let value = args.next().ok_or("--history expects a path")?;
+                    settings.history = HistoryLocation::At(PathBuf::from(value));
+                }
+                "--no-history" => settings.history = HistoryLocation::Disabled,
+                "--timbre" => {
+                    let value = args.next().ok_or("--timbre expects a value")?;
+                    settings.timbre = Timbre::parse(&value)?;
+                }
+                "--audio" => {
+                    let value = args.next().ok_or("--audio expects a value")?;
+                    settings.audio = AudioBackendKind::parse(&value)?;
+                }
+                "--input" => {
+                    let value = args.next().ok_or("--input expects a device name")?;
+                    settings.devices.input = Some(DeviceChoice::parse(&value)?);
+                }
+                "--output" => {
+                    let value = args.next().ok_or("--output expects a device name")?;
+                    settings.devices.output = Some(DeviceChoice::parse(&value)?);
+                }
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+
+        if compound {
+            settings.intervals.extend(&IntervalPool::compound());
+        }
+        if !clef_given && settings.instrument.is_none() {
+            settings.staff = range_staff;
+        }
+        if settings.chord_answer == ChordAnswerMode::Sing(ChordTone::Seventh)
+            && settings.chords != ChordSet::Sevenths
+        {
+            return Err(String::from(
+                "--chord-answer seventh needs --chords sevenths, triads have no seventh",
+            ));
+        }
+        if let Some(instrument) = settings.instrument {
+            if !range_given {
+                settings.range = instrument.range;
+            } else if range_sounding {
+                settings.range = instrument.written_range(settings.range);
+            }
+            settings.staff.get_or_insert(instrument.staff);
+            if let OctaveMatching::Transposed(_) = settings.octaves {
+                return Err(String::from(
+                    "--octaves transposed: and --instrument both transpose, pick one",
+                ));
+            }
+        }
+        if settings.spelling == SpellingMode::Key && settings.key.is_none() {
+            return Err(String::from("--spelling key needs a --key"));
+        }
+        if let (Drill::ScaleDegrees, Some(key)) = (settings.drill, settings.key) {
+            if !key.has_cadence() {
+                return Err(format!(
+                    "--drill scale-degrees needs a major or minor --key, {key} has no cadence"
+                ));
+            }
+        }
+        if settings.chromatic && settings.drill != Drill::ScaleDegrees {
+            return Err(String::from("--chromatic is for --drill scale-degrees"));
+        }
+        settings.check_range()?;
+
+        Ok(settings)
+    }
+}
+
+impl Settings {
+    /// Whether the range holds what the drill draws, for the exercises not to run out of notes
+    fn check_range(&self) -> Result<(), String> {
+        let range = &self.range;
+        match self.drill {
+            Drill::Intervals => {
+                if !self.intervals.intervals().any(|i| range.fits(i.size_i8())) {
+                    return Err(String::from(
+                        "--range is too narrow for any interval of --intervals",
+                    ));
+                }
+            }
+            Drill::Chords => {
+                let widest = self
+                    .chords
+                    .qualities()
+                    .into_iter()
+                    .flat_map(|quality| (0..quality.n_notes()).map(move |i| quality.span(i)))
+                    .max()
+                    .unwrap();
+                if !range.fits(widest) {
+                    return Err(format!(
+                        "--range is too narrow for the chords, they span up to {widest} semitones"
+                    ));
+                }
+            }
+            Drill::Melodies => {
+                let keys = match self.key {
+                    Some(key) => vec![key],
+                    None => Key::majors(),
+                };
+                if let Some(key) = keys
+                    .into_iter()
+                    .find(|&key| Melody::scale(range, key).len() < 2)
+                {
+                    return Err(match self.key {
+                        Some(_) => format!("--range has less than two notes of {key}"),
+                        None => format!(
+                            "--range has less than two notes of {key}, widen it or give a --key"
+                        ),
+                    });
+                }
+            }
+            Drill::ScaleDegrees => {}
+        }
+        Ok(())
+    }
+}
+
+impl RenderOptions {
+    /// Whether the argument was one of the render options
+    fn parse_arg(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, String> {
+        let mut value = || args.next().ok_or(format!("{arg} expects a value"));
+        let number = |value: String| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("{arg} expects a number, got {value}"))
+        };
+
+        match arg {
+            "--count" => self.count = number(value()?)? as usize,
+            "--out" => self.out = PathBuf::from(value()?),
+            "--note-length" => self.note_length = Duration::from_millis(number(value()?)?),
+            "--spacing" => self.spacing = Duration::from_millis(number(value()?)?),
+            "--sample-rate" => {
+                self.sample_rate = number(value()?)?
+                    .try_into()
+                    .map_err(|_| format!("--sample-rate is at most {}", u32::MAX))?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl Drill {
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "intervals" => Ok(Drill::Intervals),
+            "scale-degrees" => Ok(Drill::ScaleDegrees),
+            "chords" => Ok(Drill::Chords),
+            "melodies" => Ok(Drill::Melodies),
+            _ => Err(format!(
+                "invalid drill: {string} (expected intervals, scale-degrees, chords or melodies)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Drill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Drill::Intervals => "intervals",
+            Drill::ScaleDegrees => "scale-degrees",
+            Drill::Chords => "chords",
+            Drill::Melodies => "melodies",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl Leaps {
+    /// The widest move, in steps of the scale
+    pub fn max_steps(&self) -> u8 {
+        match self {
+            Leaps::Steps => 1,
+            Leaps::Thirds => 2,
+            Leaps::Fifths => 4,
+            Leaps::Octaves => 7,
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "steps" => Ok(Leaps::Steps),
+            "thirds" => Ok(Leaps::Thirds),
+            "fifths" => Ok(Leaps::Fifths),
+            "octaves" => Ok(Leaps::Octaves),
+            _ => Err(format!(
+                "invalid leaps: {string} (expected steps, thirds, fifths or octaves)"
+            )),
+        }
+    }
+}
+
+impl ChordSet {
+    pub fn qualities(&self) -> Vec<ChordQuality> {
+        match self {
+            ChordSet::Triads => ChordQuality::triads(),
+            ChordSet::Sevenths => ChordQuality::sevenths(),
+            ChordSet::All => [ChordQuality::triads(), ChordQuality::sevenths()].concat(),
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "triads" => Ok(ChordSet::Triads),
+            "sevenths" => Ok(ChordSet::Sevenths),
+            "all" => Ok(ChordSet::All),
+            _ => Err(format!(
+                "invalid chords: {string} (expected triads, sevenths or all)"
+            )),
+        }
+    }
+}
+
+impl DirectionMode {
+    pub fn pick(&self) -> Direction {
+        match self {
+            DirectionMode::Ascending => Direction::Up,
+            DirectionMode::Descending => Direction::Down,
+            DirectionMode::Mixed => Direction::rand(),
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "up" | "ascending" => Ok(DirectionMode::Ascending),
+            "down" | "descending" => Ok(DirectionMode::Descending),
+            "mixed" | "random" => Ok(DirectionMode::Mixed),
+            _ => Err(format!(
+                "invalid direction: {string} (expected up, down or mixed)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DirectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            DirectionMode::Ascending => "ascending",
+            DirectionMode::Descending => "descending",
+            DirectionMode::Mixed => "mixed",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl PresentationMode {
+    pub fn pick(&self) -> Presentation {
+        match self {
+            PresentationMode::Melodic => Presentation::Melodic,
+            PresentationMode::Harmonic => Presentation::Harmonic,
+            PresentationMode::Mixed => match rand::random() {
+                true => Presentation::Melodic,
+                false => Presentation::Harmonic,
+            },
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "melodic" => Ok(PresentationMode::Melodic),
+            "harmonic" => Ok(PresentationMode::Harmonic),
+            "mixed" => Ok(PresentationMode::Mixed),
+            _ => Err(format!(
+                "invalid presentation: {string} (expected melodic-up, melodic-down, melodic, harmonic or mixed)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PresentationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            PresentationMode::Melodic => "melodic",
+            PresentationMode::Harmonic => "harmonic",
+            PresentationMode::Mixed => "mixed",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl SpellingMode {
+    pub fn policy(&self, key: Option<Key>) -> NamingPolicy {
+        match (self, key) {
+            (SpellingMode::Auto, Some(key)) => NamingPolicy::Key(key),
+            (SpellingMode::Auto, None) => NamingPolicy::Random,
+            (SpellingMode::Key, key) => NamingPolicy::Key(key.unwrap_or_default()),
+            (SpellingMode::Sharps, _) => NamingPolicy::Sharps,
+            (SpellingMode::Flats, _) => NamingPolicy::Flats,
+            (SpellingMode::Random, _) => NamingPolicy::Random,
+            (SpellingMode::FewestAccidentals, _) => NamingPolicy::FewestAccidentals,
+        }
+    }
+
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "auto" => Ok(SpellingMode::Auto),
+            "sharps" => Ok(SpellingMode::Sharps),
+            "flats" => Ok(SpellingMode::Flats),
+            "random" => Ok(SpellingMode::Random),
+            "key" => Ok(SpellingMode::Key),
+            "fewest-accidentals" => Ok(SpellingMode::FewestAccidentals),
+            _ => Err(format!(
+                "invalid spelling: {string} (expected auto, sharps, flats, random, key or fewest-accidentals)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SpellingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            SpellingMode::Auto => "auto",
+            SpellingMode::Sharps => "sharps",
+            SpellingMode::Flats => "flats",
+            SpellingMode::Random => "random",
+            SpellingMode::Key => "key",
+            SpellingMode::FewestAccidentals => "fewest-accidentals",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+impl AudioBackendKind {
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string {
+            "device" => Ok(AudioBackendKind::Device),
+            "null" | "none" => Ok(AudioBackendKind::Null),
+            "loopback" => Ok(AudioBackendKind::Loopback),
+            _ => Err(format!(
+                "invalid audio backend: {string} (expected device, null or loopback)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AudioBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            AudioBackendKind::Device => "device",
+            AudioBackendKind::Null => "null",
+            AudioBackendKind::Loopback => "loopback",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Settings, String> {
+        let args = std::iter::once("interval_trainer")
+            .chain(args.split_whitespace())
+            .map(String::from);
+        Settings::from_args(args)
+    }
+
+    #[test]
+    fn compound_intervals_are_added_wherever_they_come() {
+        let before = parse("--compound --intervals P4,P5").unwrap();
+        let after = parse("--intervals P4,P5 --compound").unwrap();
+        let intervals = |settings: &Settings| settings.intervals.intervals().collect::<Vec<_>>();
+
+        assert_eq!(intervals(&before), intervals(&after));
+        assert!(intervals(&before).len() > 2);
+    }
+
+    #[test]
+    fn scale_degrees_are_drilled_in_major_and_minor_keys() {
+        assert!(parse("--drill scale-degrees --key F#m").is_ok());
+        assert!(parse("--key D-dorian --drill scale-degrees").is_err());
+        assert!(parse("--key D-dorian --drill melodies").is_ok());
+    }
+
+    #[test]
+    fn range_presets_are_read_as_written() {
+        let range = |args| {
+            let range = parse(args).unwrap().range;
+            format!(
+                "{}-{}",
+                range.bottom.to_note(false),
+                range.top.to_note(false)
+            )
+        };
+
+        // the sounding range of the alto sax is the written one of every saxophone
+        assert_eq!(range("--instrument alto-sax --range alto-sax"), "Bb3-F6");
+        assert_eq!(range("--range C4-C5 --instrument trumpet"), "C4-C5");
+        assert_eq!(range("--range alto-sax"), "Db3-Ab5");
+    }
+
+    #[test]
+    fn chromatic_degrees_are_for_the_scale_degree_drill() {
+        assert!(
+            parse("--drill scale-degrees --chromatic")
+                .unwrap()
+                .chromatic
+        );
+        assert!(parse("--chromatic").is_err());
+    }
+
+    #[test]
+    fn ranges_too_narrow_for_the_drill_are_refused() {
+        assert!(parse("--range C4-E4 --intervals m2,M3").is_ok());
+        assert!(parse("--range C4-E4 --intervals P5").is_err());
+        // an augmented triad in second inversion spans a minor sixth over the octave
+        assert!(parse("--range C4-E5 --drill chords").is_err());
+        assert!(parse("--range C4-F5 --drill chords").is_ok());
+        assert!(parse("--range C4-F5 --drill chords --chords sevenths").is_err());
+        assert!(parse("--range C#4-D#4 --drill melodies --key C").is_err());
+        assert!(parse("--range C4-D4 --drill melodies").is_err());
+        assert!(parse("--range C4-D4 --drill melodies --key C").is_ok());
+    }
+
+    #[test]
+    fn the_last_range_picks_the_clef_unless_one_is_given() {
+        let staff = |args| parse(args).unwrap().staff;
+        let bass = Staff::parse("bass").ok();
+
+        assert_eq!(staff("--range viola --range bass"), bass);
+        assert_eq!(staff("--range bass --range C4-C5"), None);
+        assert_eq!(staff("--clef bass --range viola"), bass);
+        assert_eq!(
+            staff("--range viola --clef auto"),
+            Staff::parse("alto").ok()
+        );
+    }
+}
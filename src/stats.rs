@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use crate::history::Record;
+use crate::music::{Direction, Interval, Presentation};
+use crate::settings::Drill;
+
+/// Intervals are compared regardless of octave, since any octave of the target is accepted
+pub const N_INTERVAL_CLASSES: usize = 12;
+
+/// Aggregated results over the interval exercises of a practice history
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    /// one row per interval that was asked at least once, smallest first
+    pub intervals: Vec<IntervalStatistics>,
+    /// `confusion[asked][sung]` counts, both as interval classes in semitones
+    pub confusion: [[u32; N_INTERVAL_CLASSES]; N_INTERVAL_CLASSES],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntervalStatistics {
+    pub interval: Interval,
+    pub attempts: u32,
+    pub successes: u32,
+    /// signed, negative means flat
+    pub average_cent_deviation: Option<f64>,
+    /// over successful attempts only, a miss always lasts the whole detection window
+    pub median_response_time: Option<Duration>,
+}
+
+impl Statistics {
+    pub fn from_records(records: &[Record]) -> Self {
+        Statistics::presented(records, None)
+    }
+
+    /// Only over the intervals played that way, all of them when `None`
+    pub fn presented(records: &[Record], presentation: Option<Presentation>) -> Self {
+        let mut statistics = Statistics::default();
+        let records: Vec<&Record> = records
+            .iter()
+            .filter(|record| record.drill == Drill::Intervals)
+            .filter(|record| presentation.is_none_or(|p| record.presentation == p))
+            .collect();
+
+        let mut intervals: Vec<Interval> = Vec::new();
+        for record in &records {
+            if !intervals.contains(&record.interval) {
+                intervals.push(record.interval);
+            }
+            if let Some(sung) = sung_interval_class(record) {
+                statistics.confusion[interval_class(record.interval)][sung] += 1;
+            }
+        }
+        intervals.sort_by_key(|i| (i.size_i8(), i.base_interval.index_i8()));
+
+        statistics.intervals = intervals
+            .into_iter()
+            .map(|interval| {
+                let records: Vec<&Record> = records
+                    .iter()
+                    .copied()
+                    .filter(|r| r.interval == interval)
+                    .collect();
+                IntervalStatistics::from_records(interval, &records)
+            })
+            .collect();
+
+        statistics
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+}
+
+impl IntervalStatistics {
+    fn from_records(interval: Interval, records: &[&Record]) -> Self {
+        let deviations: Vec<f64> = records
+            .iter()
+            .filter_map(|r| r.cent_deviation)
+            .map(f64::from)
+            .collect();
+        let average_cent_deviation = match deviations.len() {
+            0 => None,
+            n => Some(deviations.iter().sum::<f64>() / n as f64),
+        };
+
+        let mut response_times: Vec<Duration> = records
+            .iter()
+            .filter(|r| r.success)
+            .map(|r| r.response_time)
+            .collect();
+        response_times.sort();
+        let median_response_time = match response_times.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(response_times[n / 2]),
+            n => Some((response_times[n / 2 - 1] + response_times[n / 2]) / 2),
+        };
+
+        IntervalStatistics {
+            interval,
+            attempts: records.len() as u32,
+            successes: records.iter().filter(|r| r.success).count() as u32,
+            average_cent_deviation,
+            median_response_time,
+        }
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        match self.attempts {
+            0 => 0.0,
+            n => self.successes as f64 / n as f64,
+        }
+    }
+}
+
+pub fn interval_class(interval: Interval) -> usize {
+    interval.size_i8().rem_euclid(N_INTERVAL_CLASSES as i8) as usize
+}
+
+/// The interval from the reference to what was heard, in the direction of the exercise
+pub fn sung_interval_class(record: &Record) -> Option<usize> {
+    let frequency = record.detected_frequency?;
+    let semitones = (12.0 * (frequency / record.reference.frequency()).log2()).round() as i32;
+    let semitones = match record.direction {
+        Direction::Up => semitones,
+        Direction::Down => -semitones,
+    };
+
+    Some(semitones.rem_euclid(N_INTERVAL_CLASSES as i32) as usize)
+}
+
+/// Short name of an interval class, the tritone being ambiguous
+pub fn interval_class_repr(class: usize) -> &'static str {
+    [
+        "P1", "m2", "M2", "m3", "M3", "P4", "TT", "P5", "m6", "M6", "m7", "M7",
+    ][class]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::listen::OctaveMatching;
+    use crate::music::interval::{BaseInterval, Quality};
+    use crate::music::{Note, NoteName, Presentation};
+    use std::time::UNIX_EPOCH;
+
+    const A4: Note = Note { name: NoteName::A, alteration: 0, octave: 4 };
+    const FIFTH: Interval =
+        Interval { base_interval: BaseInterval::Fifth, quality: Quality::Perfect };
+
+    fn record(sung: Option<f64>, success: bool, response_time_ms: u64) -> Record {
+        Record {
+            timestamp: UNIX_EPOCH,
+            drill: Drill::Intervals,
+            reference: A4,
+            target: A4.down(FIFTH),
+            interval: FIFTH,
+            direction: Direction::Down,
+            presentation: Presentation::Melodic,
+            reference_success: true,
+            success,
+            octaves: OctaveMatching::Tolerant,
+            detected_frequency: sung,
+            cent_deviation: success.then_some(-4),
+            response_time: Duration::from_millis(response_time_ms),
+            quality: None,
+            picked: None,
+        }
+    }
+
+    #[test]
+    fn per_interval_statistics() {
+        let records = [
+            // D4, a fifth down
+            record(Some(293.66), true, 400),
+            record(Some(293.66), true, 800),
+            record(Some(293.66), true, 500),
+            // C#4, a minor sixth down
+            record(Some(277.18), false, 1500),
+        ];
+        let statistics = Statistics::from_records(&records);
+
+        assert_eq!(statistics.intervals.len(), 1);
+        let fifth = &statistics.intervals[0];
+        assert_eq!((fifth.attempts, fifth.successes), (4, 3));
+        assert_eq!(fifth.average_cent_deviation, Some(-4.0));
+        assert_eq!(fifth.median_response_time, Some(Duration::from_millis(500)));
+
+        assert_eq!(statistics.confusion[7][7], 3);
+        assert_eq!(statistics.confusion[7][8], 1);
+
+        let harmonic = Statistics::presented(&records, Some(Presentation::Harmonic));
+        assert!(harmonic.is_empty());
+        assert_eq!(
+            Statistics::presented(&records, Some(Presentation::Melodic)),
+            statistics
+        );
+    }
+}